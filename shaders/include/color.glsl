@@ -0,0 +1,14 @@
+// PSX 4x4 ordered dither offsets (in 8 bit units) applied before truncation to 5 bits per channel
+const float ditherTable[16] = float[16](
+    -4.0,  0.0, -3.0,  1.0,
+     2.0, -2.0,  3.0, -1.0,
+    -3.0,  1.0, -4.0,  0.0,
+     3.0, -1.0,  2.0, -2.0
+);
+
+vec3 dither(vec3 color)
+{
+    int index = (int(gl_FragCoord.y) & 3) * 4 + (int(gl_FragCoord.x) & 3);
+    vec3 color8 = clamp(color * 255.0 + ditherTable[index], 0.0, 255.0);
+    return floor(color8 / 8.0) / 31.0;
+}
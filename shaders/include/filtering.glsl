@@ -0,0 +1,25 @@
+uniform bool threePoint;
+
+// N64 styled 3-point bilinear filtering, the texture itself is sampled with GL_NEAREST
+vec4 threePointTexture(sampler2D tex, vec2 texCoord)
+{
+    vec2 size = vec2(textureSize(tex, 0));
+    vec2 texel = texCoord * size - 0.5;
+    vec2 frac = fract(texel);
+    vec2 base = (floor(texel) + 0.5) / size;
+
+    vec4 a = texture(tex, base);
+    vec4 b = texture(tex, base + vec2(1.0 / size.x, 0.0));
+    vec4 c = texture(tex, base + vec2(0.0, 1.0 / size.y));
+    vec4 d = texture(tex, base + 1.0 / size);
+
+    if (frac.x + frac.y <= 1.0) {
+        return a + frac.x * (b - a) + frac.y * (c - a);
+    }
+    return d + (1.0 - frac.x) * (c - d) + (1.0 - frac.y) * (b - d);
+}
+
+vec4 sampleTexture(sampler2D tex, vec2 texCoord)
+{
+    return threePoint ? threePointTexture(tex, texCoord) : texture(tex, texCoord);
+}
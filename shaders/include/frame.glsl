@@ -0,0 +1,36 @@
+// Per-frame data, filled once per frame by World::render
+layout (std140) uniform Frame {
+    mat4 viewProjection;
+    vec4 cameraPosition;
+    vec4 ambientColor;
+    vec4 lightDirection;
+    vec4 lightColor;
+    vec2 renderResolution;
+    // Dequantization of packed vertex positions, identity for float vertices
+    vec4 positionScale;
+    vec4 positionOffset;
+    // Depth cueing: display color, start and end distance in x and y,
+    // mode in z (0 linear, 1 exponential, 2 exponential squared) and density in w
+    vec4 fogColor;
+    vec4 fogRange;
+};
+
+// Mesh space position of the position attribute
+vec3 decodePosition(vec3 position)
+{
+    return position * positionScale.xyz + positionOffset.xyz;
+}
+
+// Blend towards the fog color at `depth` units from the camera
+float depthCue(float depth)
+{
+    float distance = max(depth - fogRange.x, 0.0);
+    if (fogRange.z > 1.5) {
+        float scaled = fogRange.w * distance;
+        return 1.0 - exp(-scaled * scaled);
+    }
+    if (fogRange.z > 0.5) {
+        return 1.0 - exp(-fogRange.w * distance);
+    }
+    return clamp(distance / max(fogRange.y - fogRange.x, 1e-4), 0.0, 1.0);
+}
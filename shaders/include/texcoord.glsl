@@ -0,0 +1,7 @@
+// PSX GPU had no perspective correction, AFFINE reproduces it.
+// Assets are not ready for it, so large surfaces with few triangles show major artifacts.
+#ifdef AFFINE
+#define TEXCOORD noperspective
+#else
+#define TEXCOORD smooth
+#endif
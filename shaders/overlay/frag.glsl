@@ -0,0 +1,17 @@
+#version 330 core
+in vec2 texCoord;
+in vec4 color;
+
+uniform sampler2D image;
+
+out vec4 FragColor;
+
+void main() {
+    vec4 texel = texture(image, texCoord);
+    // Like the PS1 GPU, fully transparent pixels aren't drawn
+    if (texel.a == 0.0) {
+        discard;
+    }
+
+    FragColor = texel * color;
+}
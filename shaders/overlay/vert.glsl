@@ -0,0 +1,18 @@
+#version 330 core
+#include "../include/frame.glsl"
+
+// Render resolution pixels, origin at the top left
+layout (location = 0) in vec2 vPos;
+layout (location = 2) in vec2 vTexCoord0;
+layout (location = 4) in vec4 vColor;
+
+out vec2 texCoord;
+out vec4 color;
+
+void main() {
+    vec2 ndc = vPos / renderResolution * 2.0 - 1.0;
+    gl_Position = vec4(ndc.x, -ndc.y, 0.0, 1.0);
+
+    texCoord = vTexCoord0;
+    color = vColor;
+}
@@ -0,0 +1,61 @@
+#version 330 core
+// Permutations: TEXTURED, EMISSIVE_MAP, VERTEX_COLORED, LIT, ALPHA_TEST, AFFINE, DITHERED, FOG
+#include "../include/color.glsl"
+#include "../include/frame.glsl"
+#include "../include/filtering.glsl"
+#include "../include/texcoord.glsl"
+
+in vec4 vertexColor;
+#ifdef FOG
+in float fogAmount;
+#endif
+
+uniform vec4 baseColor;
+uniform vec3 emissiveColor;
+
+#ifdef TEXTURED
+TEXCOORD in vec2 albedoTexCoord;
+
+uniform sampler2D albedo;
+#endif
+
+#ifdef EMISSIVE_MAP
+TEXCOORD in vec2 emissiveTexCoord;
+
+uniform sampler2D emissive;
+#endif
+
+#ifdef ALPHA_TEST
+uniform float alphaCutoff;
+#endif
+
+out vec4 FragColor;
+
+void main() {
+    vec4 color = baseColor * vertexColor;
+#ifdef TEXTURED
+    color *= sampleTexture(albedo, albedoTexCoord);
+#endif
+
+#ifdef ALPHA_TEST
+    if (color.a < alphaCutoff) {
+        discard;
+    }
+#endif
+
+    vec3 emission = emissiveColor;
+#ifdef EMISSIVE_MAP
+    emission *= sampleTexture(emissive, emissiveTexCoord).rgb;
+#endif
+
+    vec3 result = color.rgb + emission;
+#ifdef FOG
+    // The fog color is a display color like the clear color
+    result = mix(result, fogColor.rgb, fogAmount);
+#endif
+#ifdef DITHERED
+    result = dither(result);
+#endif
+
+    FragColor = vec4(result, color.a);
+}
@@ -0,0 +1,82 @@
+#version 330 core
+// Permutations: TEXTURED, EMISSIVE_MAP, VERTEX_COLORED, LIT, ALPHA_TEST, AFFINE, DITHERED, INSTANCED, FOG
+#include "../include/frame.glsl"
+#include "../include/snap.glsl"
+#include "../include/texcoord.glsl"
+
+layout (location = 0) in vec3 vPos;
+layout (location = 1) in vec3 vNormal;
+layout (location = 2) in vec2 vTexCoord0;
+layout (location = 3) in vec2 vTexCoord1;
+layout (location = 4) in vec4 vColor;
+
+out vec4 vertexColor;
+#ifdef FOG
+out float fogAmount;
+#endif
+
+#ifdef INSTANCED
+layout (location = 5) in mat4 instanceModel;
+#define model instanceModel
+#else
+uniform mat4 model;
+#endif
+
+#ifdef LIT
+#ifdef INSTANCED
+layout (location = 9) in mat3 instanceNormalMatrix;
+#define normalMatrix instanceNormalMatrix
+#else
+uniform mat3 normalMatrix;
+#endif
+#endif
+
+#ifdef TEXTURED
+TEXCOORD out vec2 albedoTexCoord;
+
+uniform int albedoTexCoordSet;
+uniform mat3 albedoTransform;
+#endif
+
+#ifdef EMISSIVE_MAP
+TEXCOORD out vec2 emissiveTexCoord;
+
+uniform int emissiveTexCoordSet;
+uniform mat3 emissiveTransform;
+#endif
+
+// set: TEXCOORD_n attribute index, transform: KHR_texture_transform matrix
+vec2 texCoord(int set, mat3 transform)
+{
+    vec2 texCoord = set == 0 ? vTexCoord0 : vTexCoord1;
+    return (transform * vec3(texCoord, 1.0)).xy;
+}
+
+void main() {
+    vec4 worldPosition = model * vec4(decodePosition(vPos), 1.0);
+    gl_Position = snap(viewProjection * worldPosition, renderResolution);
+
+#ifdef FOG
+    fogAmount = depthCue(distance(worldPosition.xyz, cameraPosition.xyz));
+#endif
+
+    vertexColor = vec4(1.0);
+#ifdef VERTEX_COLORED
+    vertexColor = vColor;
+#endif
+
+#ifdef LIT
+    // Gouraud shading
+    vec3 normal = normalize(normalMatrix * vNormal);
+    float diffuse = max(dot(normal, -lightDirection.xyz), 0.0);
+    vertexColor.rgb *= ambientColor.rgb + diffuse * lightColor.rgb;
+#endif
+
+#ifdef TEXTURED
+    albedoTexCoord = texCoord(albedoTexCoordSet, albedoTransform);
+#endif
+
+#ifdef EMISSIVE_MAP
+    emissiveTexCoord = texCoord(emissiveTexCoordSet, emissiveTransform);
+#endif
+}
@@ -0,0 +1,47 @@
+#version 330 core
+// Permutations: CUBEMAP, EQUIRECT, BACKDROP, FOG
+#include "../include/frame.glsl"
+
+#ifdef CUBEMAP
+uniform samplerCube sky;
+#else
+uniform sampler2D sky;
+#endif
+
+#ifdef BACKDROP
+in vec2 backdropTexCoord;
+#ifdef FOG
+in float elevation;
+#endif
+#else
+in vec3 direction;
+#endif
+
+out vec4 FragColor;
+
+const float PI = 3.14159265;
+// Angle above the horizon where the fog has cleared, in radians
+const float HORIZON_FOG = 0.3;
+
+void main() {
+#if defined(CUBEMAP)
+    vec3 color = texture(sky, direction).rgb;
+#elif defined(EQUIRECT)
+    vec3 d = normalize(direction);
+    vec2 texCoord = vec2(atan(d.x, -d.z) / (2.0 * PI) + 0.5, acos(clamp(d.y, -1.0, 1.0)) / PI);
+    // No derivatives, they jump at the seam
+    vec3 color = textureLod(sky, texCoord, 0.0).rgb;
+#else
+    vec3 color = texture(sky, backdropTexCoord).rgb;
+#endif
+
+#ifdef FOG
+#ifndef BACKDROP
+    float elevation = asin(clamp(normalize(direction).y, -1.0, 1.0));
+#endif
+    // Geometry is fully fogged long before the sky, it only clears up above the horizon
+    color = mix(color, fogColor.rgb, 1.0 - smoothstep(0.0, HORIZON_FOG, elevation));
+#endif
+
+    FragColor = vec4(color, 1.0);
+}
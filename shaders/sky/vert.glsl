@@ -0,0 +1,41 @@
+#version 330 core
+// Permutations: CUBEMAP, EQUIRECT, BACKDROP, FOG
+#include "../include/frame.glsl"
+#include "../include/snap.glsl"
+
+#ifdef BACKDROP
+// Texture coordinates of the bottom left and top right corners of the viewport
+uniform vec4 backdropRect;
+
+out vec2 backdropTexCoord;
+#ifdef FOG
+// Angles above the horizon at the bottom and top of the viewport
+uniform vec2 backdropElevation;
+
+out float elevation;
+#endif
+#else
+layout (location = 0) in vec3 vPos;
+
+// Projection and rotation of the view, the sky is infinitely far away
+uniform mat4 skyViewProjection;
+
+out vec3 direction;
+#endif
+
+void main() {
+#ifdef BACKDROP
+    // Triangle covering the viewport, corner is (0, 0), (2, 0) or (0, 2)
+    vec2 corner = vec2((gl_VertexID << 1) & 2, gl_VertexID & 2);
+    backdropTexCoord = mix(backdropRect.xy, backdropRect.zw, corner);
+#ifdef FOG
+    elevation = mix(backdropElevation.x, backdropElevation.y, corner.y);
+#endif
+    gl_Position = vec4(corner * 2.0 - 1.0, 0.0, 1.0);
+#else
+    direction = vPos;
+    vec4 position = skyViewProjection * vec4(vPos, 1.0);
+    // Snapping divides by w, corners beside the camera are outside of the view anyway
+    gl_Position = position.w > 1e-3 ? snap(position, renderResolution) : position;
+#endif
+}
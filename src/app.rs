@@ -0,0 +1,449 @@
+use std::path::{Path, PathBuf};
+
+use crate::camera::{CameraMode, ProjectionType};
+use crate::camera_path::CameraPath;
+use crate::framebuffer::FramebufferError;
+use crate::input::{Action, Bindings, InputState};
+use crate::platform::{Platform, PlatformEvent, StickAxis, SwapInterval};
+use crate::render::{RenderStats, World};
+use crate::texture::FilterPolicy;
+
+pub const CAMERA_PATH: &str = "camera_path.txt";
+/// Degrees per field of view step
+const FOV_STEP: f32 = 5.0;
+/// Longest frame the simulation catches up on, slower frames slow the simulation down
+const MAX_FRAME_TIME: f64 = 0.25;
+
+/// Presented frame rate limit, PS1 games commonly ran at 30 or 20 fps
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum RenderThrottle {
+    #[default]
+    Off,
+    Fps30,
+    Fps20,
+}
+
+impl RenderThrottle {
+    pub fn next(self) -> Self {
+        match self {
+            Self::Off => Self::Fps30,
+            Self::Fps30 => Self::Fps20,
+            Self::Fps20 => Self::Off,
+        }
+    }
+
+    fn frame_rate(self) -> Option<f64> {
+        match self {
+            Self::Off => None,
+            Self::Fps30 => Some(30.0),
+            Self::Fps20 => Some(20.0),
+        }
+    }
+}
+
+/// Simulation rate and frame presentation settings of `run`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FramePacing {
+    /// Simulation updates per second, independent of the frame rate
+    pub tick_rate: f64,
+    pub swap_interval: SwapInterval,
+    /// Frames per second limit, e.g. when vsync is off
+    pub frame_cap: Option<f64>,
+    pub throttle: RenderThrottle,
+}
+
+impl Default for FramePacing {
+    fn default() -> Self {
+        Self {
+            tick_rate: 60.0,
+            swap_interval: SwapInterval::default(),
+            frame_cap: None,
+            throttle: RenderThrottle::default(),
+        }
+    }
+}
+
+impl FramePacing {
+    /// Shortest time between presented frames
+    fn min_frame_time(&self) -> Option<f64> {
+        [self.frame_cap, self.throttle.frame_rate()]
+            .into_iter()
+            .flatten()
+            .map(|rate| 1.0 / rate)
+            .reduce(f64::max)
+    }
+}
+
+/// What the app did in response to the user, for the host to show or act on
+#[derive(Debug, Clone, PartialEq)]
+pub enum Status {
+    FilterPolicy(FilterPolicy),
+    FrameRateLimit(RenderThrottle),
+    CameraMode(CameraMode),
+    Projection(ProjectionType),
+    FieldOfView(cgmath::Deg<f32>),
+    RecordingStarted,
+    CameraPathSaved,
+    CameraPathSaveFailed(String),
+    CameraPathLoadFailed(String),
+    PlaybackFinished,
+    NothingToFrame,
+    Stats(RenderStats),
+    /// Size and RGBA pixels of the low resolution frame, top row first
+    Screenshot((u32, u32), Vec<u8>),
+    ScreenshotFailed(FramebufferError),
+    ShadersReloaded {
+        programs: usize,
+        changed: Vec<PathBuf>,
+    },
+    /// Log of a program that failed to rebuild, it keeps its previous version
+    ShaderReloadFailed(String),
+    ControllerConnected(String),
+    ControllerFailed(String),
+    /// The platform refused the swap interval, adaptive vsync falls back to vsync
+    SwapIntervalFailed(SwapInterval, String),
+}
+
+impl Status {
+    pub fn is_error(&self) -> bool {
+        matches!(
+            self,
+            Self::CameraPathSaveFailed(_)
+                | Self::CameraPathLoadFailed(_)
+                | Self::ScreenshotFailed(_)
+                | Self::ShaderReloadFailed(_)
+                | Self::ControllerFailed(_)
+                | Self::SwapIntervalFailed(..)
+        )
+    }
+}
+
+impl std::fmt::Display for Status {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::FilterPolicy(policy) => write!(f, "Texture filtering: {policy:?}"),
+            Self::FrameRateLimit(throttle) => write!(f, "Frame rate limit: {throttle:?}"),
+            Self::CameraMode(mode) => write!(f, "Camera mode: {mode:?}"),
+            Self::Projection(projection) => write!(f, "Projection: {projection:?}"),
+            Self::FieldOfView(fovy) => write!(f, "Field of view: {fovy:?}"),
+            Self::RecordingStarted => write!(f, "Recording camera path"),
+            Self::CameraPathSaved => write!(f, "Camera path saved to {CAMERA_PATH}"),
+            Self::CameraPathSaveFailed(error) => write!(f, "Can't save camera path: {error}"),
+            Self::CameraPathLoadFailed(error) => write!(f, "Can't load camera path: {error}"),
+            Self::PlaybackFinished => write!(f, "Camera path playback finished"),
+            Self::NothingToFrame => write!(f, "Nothing to frame in the center of the view"),
+            Self::Stats(stats) => write!(
+                f,
+                "Primitives: {} drawn, {} culled. Draw calls: {}, state changes: {}",
+                stats.drawn, stats.culled, stats.draw_calls, stats.state_changes
+            ),
+            Self::Screenshot((width, height), _) => write!(f, "Screenshot {width}x{height}"),
+            Self::ScreenshotFailed(error) => write!(f, "Can't take screenshot: {error}"),
+            Self::ShadersReloaded { programs, changed } => {
+                write!(f, "Reloaded {programs} programs using {changed:?}")
+            }
+            Self::ShaderReloadFailed(log) => {
+                write!(f, "Shader reload failed, keeping previous program\n{log}")
+            }
+            Self::ControllerConnected(name) => write!(f, "Game controller connected: {name}"),
+            Self::ControllerFailed(error) => write!(f, "Can't open game controller: {error}"),
+            Self::SwapIntervalFailed(interval, error) => {
+                write!(f, "Can't set swap interval {interval:?}: {error}")
+            }
+        }
+    }
+}
+
+/// Scene viewer driven by platform events, independent of the host
+#[derive(Debug)]
+pub struct App {
+    world: World,
+    bindings: Bindings,
+    input: InputState,
+    sticks: Sticks,
+    pacing: FramePacing,
+    running: bool,
+    /// Reported since the last `take_status`
+    status: Vec<Status>,
+}
+
+impl App {
+    /// Expects the GL context of the host to be current
+    pub fn new<P: AsRef<Path>>(scene_path: P, bindings: Bindings) -> Result<Self, String> {
+        let mut world = World::from_gltf_file(scene_path)?;
+        world.set_look_speed(bindings.look_speed);
+
+        Ok(Self {
+            world,
+            bindings,
+            input: InputState::default(),
+            sticks: Sticks::default(),
+            pacing: FramePacing::default(),
+            running: true,
+            status: Vec::new(),
+        })
+    }
+
+    pub fn world(&self) -> &World {
+        &self.world
+    }
+
+    pub fn world_mut(&mut self) -> &mut World {
+        &mut self.world
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running
+    }
+
+    pub fn pacing(&self) -> FramePacing {
+        self.pacing
+    }
+
+    pub fn set_pacing(&mut self, pacing: FramePacing) {
+        self.pacing = pacing;
+    }
+
+    /// Status reported since the previous call, oldest first
+    pub fn take_status(&mut self) -> Vec<Status> {
+        std::mem::take(&mut self.status)
+    }
+
+    pub fn handle_event(&mut self, event: PlatformEvent) {
+        match event {
+            PlatformEvent::Quit => self.running = false,
+            PlatformEvent::Resized(width, height) => {
+                self.world
+                    .update_viewport(0, 0, width as i32, height as i32)
+            }
+            PlatformEvent::KeyDown(name) => {
+                if let Some(action) = self.bindings.key(&name) {
+                    self.input.press(action);
+                }
+            }
+            PlatformEvent::KeyUp(name) => {
+                if let Some(action) = self.bindings.key(&name) {
+                    self.input.release(action);
+                }
+            }
+            PlatformEvent::ButtonDown(name) => {
+                if let Some(action) = self.bindings.button(&name) {
+                    self.input.press(action);
+                }
+            }
+            PlatformEvent::ButtonUp(name) => {
+                if let Some(action) = self.bindings.button(&name) {
+                    self.input.release(action);
+                }
+            }
+            PlatformEvent::StickMoved(axis, value) => self.sticks.set(axis, value),
+            PlatformEvent::ControllerAdded(name) => {
+                self.status.push(Status::ControllerConnected(name))
+            }
+            PlatformEvent::ControllerFailed(error) => {
+                self.status.push(Status::ControllerFailed(error))
+            }
+            PlatformEvent::ControllerRemoved => self.sticks = Sticks::default(),
+            PlatformEvent::MouseMotion(x, y) => {
+                self.input.mouse_rel.0 += x;
+                self.input.mouse_rel.1 += y;
+            }
+            PlatformEvent::MouseWheel(y) => self.input.wheel += y,
+        }
+    }
+
+    /// Advances the simulation by `delta` seconds,
+    /// consumes the input received since the previous update
+    pub fn update(&mut self, delta: f32) {
+        // Stick y axes point down, movement forward is up
+        let (move_x, move_y) = self.bindings.stick(self.sticks.left);
+        self.input.move_axis = (move_x, -move_y);
+        self.input.look_axis = self.bindings.stick(self.sticks.right);
+
+        for action in self.input.pressed().to_vec() {
+            self.trigger(action);
+        }
+
+        if self.running {
+            let playing = self.world.is_playing();
+            self.world.update(&self.input, delta);
+            if playing && !self.world.is_playing() {
+                self.status.push(Status::PlaybackFinished);
+            }
+        }
+
+        self.input.begin_frame();
+    }
+
+    /// Relinks programs whose shader files changed, called once per frame
+    pub fn reload_shaders(&mut self) {
+        let Some(reload) = self.world.reload_shaders() else {
+            return;
+        };
+
+        if reload.relinked > 0 {
+            self.status.push(Status::ShadersReloaded {
+                programs: reload.relinked,
+                changed: reload.changed,
+            });
+        }
+        self.status
+            .extend(reload.errors.into_iter().map(Status::ShaderReloadFailed));
+    }
+
+    /// Renders `alpha` of the way from the previous to the latest update
+    pub fn render(&mut self, alpha: f32) {
+        self.world.render_interpolated(alpha);
+    }
+
+    fn trigger(&mut self, action: Action) {
+        let world = &mut self.world;
+        let status = &mut self.status;
+
+        match action {
+            Action::Quit => self.running = false,
+            Action::CycleFilter => {
+                let policy = world.filter_policy().next();
+                world.set_filter_policy(policy);
+                status.push(Status::FilterPolicy(policy));
+            }
+            Action::CycleFrameRate => {
+                self.pacing.throttle = self.pacing.throttle.next();
+                status.push(Status::FrameRateLimit(self.pacing.throttle));
+            }
+            Action::ToggleCameraMode => {
+                let mut mode = world.camera_mode().next();
+                if mode == CameraMode::Fixed && !world.has_fixed_cameras() {
+                    mode = mode.next();
+                }
+                world.set_camera_mode(mode);
+                status.push(Status::CameraMode(mode));
+            }
+            Action::ToggleRecording => match world.stop_recording() {
+                Some(path) => status.push(match path.save(CAMERA_PATH) {
+                    Ok(()) => Status::CameraPathSaved,
+                    Err(error) => Status::CameraPathSaveFailed(error),
+                }),
+                None => {
+                    world.start_recording();
+                    status.push(Status::RecordingStarted);
+                }
+            },
+            Action::TogglePlayback => {
+                if world.is_playing() {
+                    world.stop_playback();
+                } else {
+                    match CameraPath::load(CAMERA_PATH) {
+                        Ok(path) => world.play_camera_path(path, false),
+                        Err(error) => status.push(Status::CameraPathLoadFailed(error)),
+                    }
+                }
+            }
+            Action::ToggleProjection => {
+                let camera = world.camera_mut();
+                let projection = match camera.projection_type() {
+                    ProjectionType::Perspective => ProjectionType::Orthographic,
+                    ProjectionType::Orthographic => ProjectionType::Perspective,
+                };
+                camera.set_projection_type(projection);
+                status.push(Status::Projection(projection));
+            }
+            Action::FovDown | Action::FovUp => {
+                let camera = world.camera_mut();
+                let step = if action == Action::FovDown {
+                    -FOV_STEP
+                } else {
+                    FOV_STEP
+                };
+                camera.set_fovy(camera.fovy() + cgmath::Deg(step));
+                status.push(Status::FieldOfView(camera.fovy()));
+            }
+            Action::FrameScene => world.frame_scene(),
+            Action::FrameSelected => {
+                let framed = world.frame_selected();
+                if !framed {
+                    status.push(Status::NothingToFrame);
+                }
+            }
+            Action::Screenshot => status.push(match world.screenshot() {
+                Ok((size, pixels)) => Status::Screenshot(size, pixels),
+                Err(error) => Status::ScreenshotFailed(error),
+            }),
+            Action::PrintStats => status.push(Status::Stats(world.render_stats())),
+            // Held actions, read by World::update
+            _ => {}
+        }
+    }
+}
+
+/// Runs the app until it quits, the platform's context has to be current.
+///
+/// The simulation advances in fixed steps and rendering blends between the last two,
+/// frames are paced by the swap interval and `FramePacing`'s limits.
+/// `on_status` receives the app's status once per frame.
+pub fn run(platform: &mut impl Platform, app: &mut App, mut on_status: impl FnMut(Status)) {
+    let swap_interval = app.pacing().swap_interval;
+    if let Err(error) = platform.set_swap_interval(swap_interval) {
+        on_status(Status::SwapIntervalFailed(swap_interval, error));
+        if swap_interval == SwapInterval::Adaptive {
+            if let Err(error) = platform.set_swap_interval(SwapInterval::VSync) {
+                on_status(Status::SwapIntervalFailed(SwapInterval::VSync, error));
+            }
+        }
+    }
+
+    let (width, height) = platform.size();
+    app.handle_event(PlatformEvent::Resized(width, height));
+
+    let mut previous = platform.time();
+    let mut accumulator = 0.0;
+
+    while app.is_running() {
+        let frame_start = platform.time();
+        while let Some(event) = platform.poll_event() {
+            app.handle_event(event);
+        }
+        app.reload_shaders();
+
+        let step = 1.0 / app.pacing().tick_rate;
+        accumulator += (frame_start - previous).min(MAX_FRAME_TIME);
+        previous = frame_start;
+
+        while accumulator >= step && app.is_running() {
+            app.update(step as f32);
+            accumulator -= step;
+        }
+        app.take_status().into_iter().for_each(&mut on_status);
+        if !app.is_running() {
+            break;
+        }
+
+        app.render((accumulator / step) as f32);
+        platform.swap_buffers();
+
+        if let Some(min_frame_time) = app.pacing().min_frame_time() {
+            let remaining = frame_start + min_frame_time - platform.time();
+            if remaining > 0.0 {
+                platform.sleep(remaining);
+            }
+        }
+    }
+}
+
+/// Raw game controller stick positions, -1 to 1
+#[derive(Debug, Default)]
+struct Sticks {
+    left: (f32, f32),
+    right: (f32, f32),
+}
+
+impl Sticks {
+    fn set(&mut self, axis: StickAxis, value: f32) {
+        match axis {
+            StickAxis::LeftX => self.left.0 = value,
+            StickAxis::LeftY => self.left.1 = value,
+            StickAxis::RightX => self.right.0 = value,
+            StickAxis::RightY => self.right.1 = value,
+        }
+    }
+}
@@ -0,0 +1,172 @@
+use cgmath::prelude::*;
+use cgmath::Vector3;
+
+/// Axis aligned bounding box
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: Vector3<f32>,
+    pub max: Vector3<f32>,
+}
+
+impl Aabb {
+    /// Contains nothing, extending it with a point gives the point itself
+    pub fn empty() -> Self {
+        Self {
+            min: cgmath::vec3(f32::INFINITY, f32::INFINITY, f32::INFINITY),
+            max: cgmath::vec3(f32::NEG_INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY),
+        }
+    }
+
+    pub fn from_points<'a>(points: impl IntoIterator<Item = &'a Vector3<f32>>) -> Self {
+        points
+            .into_iter()
+            .fold(Self::empty(), |aabb, &point| aabb.extend(point))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+    }
+
+    pub fn extend(self, point: Vector3<f32>) -> Self {
+        Self {
+            min: cgmath::vec3(
+                self.min.x.min(point.x),
+                self.min.y.min(point.y),
+                self.min.z.min(point.z),
+            ),
+            max: cgmath::vec3(
+                self.max.x.max(point.x),
+                self.max.y.max(point.y),
+                self.max.z.max(point.z),
+            ),
+        }
+    }
+
+    pub fn union(self, other: Self) -> Self {
+        if other.is_empty() {
+            return self;
+        }
+
+        self.extend(other.min).extend(other.max)
+    }
+
+    /// Bounds of the transformed corners
+    pub fn transform(&self, transform: cgmath::Matrix4<f32>) -> Self {
+        if self.is_empty() {
+            return *self;
+        }
+
+        (0..8)
+            .map(|corner| {
+                let corner = cgmath::vec3(
+                    if corner & 1 == 0 {
+                        self.min.x
+                    } else {
+                        self.max.x
+                    },
+                    if corner & 2 == 0 {
+                        self.min.y
+                    } else {
+                        self.max.y
+                    },
+                    if corner & 4 == 0 {
+                        self.min.z
+                    } else {
+                        self.max.z
+                    },
+                );
+                (transform * corner.extend(1.0)).truncate()
+            })
+            .fold(Self::empty(), Self::extend)
+    }
+
+    pub fn center(&self) -> Vector3<f32> {
+        (self.min + self.max) * 0.5
+    }
+
+    /// Radius of the bounding sphere around the center
+    pub fn radius(&self) -> f32 {
+        (self.max - self.min).magnitude() * 0.5
+    }
+
+    /// Distance from the point to the closest point of the box, 0 inside
+    pub fn distance(&self, point: Vector3<f32>) -> f32 {
+        if self.is_empty() {
+            return f32::INFINITY;
+        }
+
+        let closest = cgmath::vec3(
+            point.x.clamp(self.min.x, self.max.x),
+            point.y.clamp(self.min.y, self.max.y),
+            point.z.clamp(self.min.z, self.max.z),
+        );
+        (closest - point).magnitude()
+    }
+
+    /// Distance along the ray to the box (0 if the origin is inside), slab method
+    pub fn ray_distance(&self, origin: Vector3<f32>, direction: Vector3<f32>) -> Option<f32> {
+        let mut near = 0.0f32;
+        let mut far = f32::INFINITY;
+
+        for axis in 0..3 {
+            let inverse = 1.0 / direction[axis];
+            let a = (self.min[axis] - origin[axis]) * inverse;
+            let b = (self.max[axis] - origin[axis]) * inverse;
+
+            near = near.max(a.min(b));
+            far = far.min(a.max(b));
+        }
+
+        (near <= far).then_some(near)
+    }
+}
+
+/// View volume as six inward facing planes `(normal, distance)`,
+/// a point is inside when `normal.dot(point) + distance >= 0` for every plane
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Frustum {
+    planes: [cgmath::Vector4<f32>; 6],
+}
+
+impl Frustum {
+    /// Planes of a view projection matrix with clip space depth from -1 to 1 (Gribb-Hartmann).
+    /// An infinite far plane degenerates into a plane every point is inside of.
+    pub fn from_matrix(view_projection: cgmath::Matrix4<f32>) -> Self {
+        let row = |i: usize| view_projection.row(i);
+        let (x, y, z, w) = (row(0), row(1), row(2), row(3));
+
+        Self {
+            planes: [w + x, w - x, w + y, w - y, w + z, w - z],
+        }
+    }
+
+    /// Conservative test, boxes near the corners of the frustum may pass while outside
+    pub fn intersects(&self, aabb: &Aabb) -> bool {
+        if aabb.is_empty() {
+            return false;
+        }
+
+        self.planes.iter().all(|plane| {
+            // Corner furthest along the plane normal
+            let corner = cgmath::vec3(
+                if plane.x >= 0.0 {
+                    aabb.max.x
+                } else {
+                    aabb.min.x
+                },
+                if plane.y >= 0.0 {
+                    aabb.max.y
+                } else {
+                    aabb.min.y
+                },
+                if plane.z >= 0.0 {
+                    aabb.max.z
+                } else {
+                    aabb.min.z
+                },
+            );
+
+            plane.truncate().dot(corner) + plane.w >= 0.0
+        })
+    }
+}
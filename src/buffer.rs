@@ -1,9 +1,11 @@
+use crate::leak::{self, GlKind};
 use crate::GlObject;
 
 pub enum BufferType {
     Array,
     ElementArray,
     Texture,
+    Uniform,
 }
 
 impl From<BufferType> for gl::types::GLenum {
@@ -12,6 +14,7 @@ impl From<BufferType> for gl::types::GLenum {
             BufferType::Array => gl::ARRAY_BUFFER,
             BufferType::ElementArray => gl::ELEMENT_ARRAY_BUFFER,
             BufferType::Texture => gl::TEXTURE_BUFFER,
+            BufferType::Uniform => gl::UNIFORM_BUFFER,
         }
     }
 }
@@ -35,6 +38,9 @@ impl From<DrawType> for gl::types::GLenum {
 pub enum ObjectType {
     Float,
     Int,
+    Short,
+    Byte,
+    UnsignedByte,
 }
 
 impl From<ObjectType> for gl::types::GLenum {
@@ -42,6 +48,9 @@ impl From<ObjectType> for gl::types::GLenum {
         match object_type {
             ObjectType::Float => gl::FLOAT,
             ObjectType::Int => gl::INT,
+            ObjectType::Short => gl::SHORT,
+            ObjectType::Byte => gl::BYTE,
+            ObjectType::UnsignedByte => gl::UNSIGNED_BYTE,
         }
     }
 }
@@ -79,17 +88,22 @@ impl Vbo {
             gl::GenBuffers(1, &mut vbo);
 
             assert_ne!(vbo, 0);
+            leak::created(GlKind::Buffer);
 
             Self(vbo)
         }
     }
 
-    pub fn set_attrib_ptr(
+    /// Attribute of interleaved vertices, `offset` bytes into each `stride` bytes long vertex.
+    /// Integer types are converted to floats, to -1..1 or 0..1 when normalized.
+    pub fn set_interleaved_attrib_ptr(
         &mut self,
         location: u32,
         size: i32,
         object_type: ObjectType,
         normalized: bool,
+        stride: usize,
+        offset: usize,
     ) {
         unsafe {
             self.bind();
@@ -98,12 +112,41 @@ impl Vbo {
                 size,
                 object_type.into(),
                 if normalized { gl::TRUE } else { gl::FALSE },
-                0,
-                std::ptr::null(),
+                stride as i32,
+                offset as *const _,
             );
             gl::EnableVertexAttribArray(location);
         }
     }
+
+    /// Points `columns` consecutive locations at the columns of float matrices starting at
+    /// `offset` bytes, `stride` bytes apart, advancing once per instance
+    pub fn set_instance_matrix_ptr(
+        &mut self,
+        location: u32,
+        (columns, rows): (usize, usize),
+        stride: usize,
+        offset: usize,
+    ) {
+        let column_size = rows * std::mem::size_of::<f32>();
+
+        unsafe {
+            self.bind();
+            for column in 0..columns {
+                let location = location + column as u32;
+                gl::VertexAttribPointer(
+                    location,
+                    rows as i32,
+                    gl::FLOAT,
+                    gl::FALSE,
+                    stride as i32,
+                    (offset + column * column_size) as *const _,
+                );
+                gl::VertexAttribDivisor(location, 1);
+                gl::EnableVertexAttribArray(location);
+            }
+        }
+    }
 }
 
 impl GlObject for Vbo {
@@ -116,6 +159,15 @@ impl Buffer for Vbo {
     const BUFFER_TYPE: BufferType = BufferType::Array;
 }
 
+impl Drop for Vbo {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteBuffers(1, &self.0);
+        }
+        leak::deleted(GlKind::Buffer);
+    }
+}
+
 #[derive(Debug)]
 #[repr(transparent)]
 pub(crate) struct Ebo(u32);
@@ -127,6 +179,7 @@ impl Ebo {
             gl::GenBuffers(1, &mut ebo);
 
             assert_ne!(ebo, 0);
+            leak::created(GlKind::Buffer);
 
             Self(ebo)
         }
@@ -142,3 +195,57 @@ impl GlObject for Ebo {
 impl Buffer for Ebo {
     const BUFFER_TYPE: BufferType = BufferType::ElementArray;
 }
+
+impl Drop for Ebo {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteBuffers(1, &self.0);
+        }
+        leak::deleted(GlKind::Buffer);
+    }
+}
+
+/// Uniform buffer, the data layout is expected to follow std140 rules
+#[derive(Debug)]
+#[repr(transparent)]
+pub(crate) struct Ubo(u32);
+
+impl Ubo {
+    pub fn new() -> Self {
+        unsafe {
+            let mut ubo = 0;
+            gl::GenBuffers(1, &mut ubo);
+
+            assert_ne!(ubo, 0);
+            leak::created(GlKind::Buffer);
+
+            Self(ubo)
+        }
+    }
+
+    /// Makes the buffer visible to uniform blocks connected to `binding`
+    pub fn bind_base(&mut self, binding: u32) {
+        unsafe {
+            gl::BindBufferBase(gl::UNIFORM_BUFFER, binding, self.0);
+        }
+    }
+}
+
+impl GlObject for Ubo {
+    fn glid(&self) -> gl::types::GLuint {
+        self.0
+    }
+}
+
+impl Buffer for Ubo {
+    const BUFFER_TYPE: BufferType = BufferType::Uniform;
+}
+
+impl Drop for Ubo {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteBuffers(1, &self.0);
+        }
+        leak::deleted(GlKind::Buffer);
+    }
+}
@@ -1,29 +1,120 @@
+use crate::bounds::Aabb;
 use crate::framebuffer::Framebuffer;
 
 use cgmath::prelude::*;
 use cgmath::Vector3;
 
+/// How movement input is applied, switchable at runtime
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum CameraMode {
+    /// WASD moves, QE moves down/up, mouse looks around
+    #[default]
+    Fly,
+    /// Mouse rotates around the target, WS zoom, AD and QE pan the target
+    Orbit,
+    /// The scene's fixed shots are shown, the camera moves like in `Fly` and is the tracked point
+    Fixed,
+    /// First person at player height with gravity and collisions, the camera only looks around
+    Walk,
+}
+
+impl CameraMode {
+    pub fn next(self) -> Self {
+        match self {
+            Self::Fly => Self::Orbit,
+            Self::Orbit => Self::Fixed,
+            Self::Fixed => Self::Walk,
+            Self::Walk => Self::Fly,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ProjectionType {
+    #[default]
+    Perspective,
+    /// Shows as much as the perspective projection does at the orbit distance,
+    /// so zooming and framing behave the same. Useful for isometric views.
+    Orthographic,
+}
+
+/// Everything that places the camera in the scene, angles as shown to users
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CameraPose {
+    pub position: Vector3<f32>,
+    pub yaw: cgmath::Deg<f32>,
+    pub pitch: cgmath::Deg<f32>,
+    pub fovy: cgmath::Deg<f32>,
+}
+
+impl CameraPose {
+    /// Blend towards `other`, yaw turns the short way
+    pub fn lerp(self, other: Self, amount: f32) -> Self {
+        let mix = |a: f32, b: f32| a + (b - a) * amount;
+        let turn = (other.yaw - self.yaw).0;
+        let turn = turn - 360.0 * (turn / 360.0).round();
+
+        Self {
+            position: self.position.lerp(other.position, amount),
+            yaw: cgmath::Deg(self.yaw.0 + turn * amount),
+            pitch: cgmath::Deg(mix(self.pitch.0, other.pitch.0)),
+            fovy: cgmath::Deg(mix(self.fovy.0, other.fovy.0)),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Camera {
     position: Vector3<f32>,
     front: Vector3<f32>,
-    up: Vector3<f32>,
     yaw: cgmath::Rad<f32>,
     pitch: cgmath::Rad<f32>,
 
+    mode: CameraMode,
+    /// Orbit center and distance to it, kept while flying so orbiting can be resumed
+    target: Vector3<f32>,
+    distance: f32,
+
+    projection_type: ProjectionType,
     fovy: cgmath::Deg<f32>,
+    z_near: f32,
+    /// None is an infinite far plane (perspective only)
+    z_far: Option<f32>,
+    /// Near plane at depth 1 and far plane at 0, depth test has to be GREATER
+    reversed_depth: bool,
     aspect: f32,
     pub(crate) dimensions: (u32, u32),
     pub(crate) framebuffer: Framebuffer,
 }
 
-const Z_NEAR: f32 = 0.1;
-const Z_FAR: f32 = 100.0;
+pub const Z_NEAR: f32 = 0.1;
+pub const Z_FAR: f32 = 100.0;
+/// Far plane of orthographic projections when the far plane is infinite
+const ORTHOGRAPHIC_FAR: f32 = 1000.0;
+/// Closest near plane, depth precision is lost quickly below it
+const MIN_Z_NEAR: f32 = 1e-3;
+/// Far plane at least this far behind the near plane
+const MIN_CLIP_DEPTH: f32 = 1e-3;
+
+pub(crate) const WORLD_UP: Vector3<f32> = cgmath::vec3(0.0, 1.0, 0.0);
 
 const MOVEMENT_SPEED: f32 = 2.0;
-const MOUSE_SENSIVITY: f32 = 100.0;
+/// Radians per unit of the `mouse` argument of `Camera::update`
+pub(crate) const MOUSE_SENSIVITY: f32 = 100.0;
 const PITCH_BOUND: f32 = 0.85 * (0.5 * std::f32::consts::PI);
 
+/// Orbit panning per second relative to the distance, so it feels the same at any zoom
+const PAN_SPEED: f32 = 0.5;
+/// Zoom per second with WS (exponential, distance is multiplied by e^-speed)
+const ZOOM_SPEED: f32 = 1.5;
+/// Zoom per mouse wheel step
+const WHEEL_ZOOM: f32 = 0.1;
+const MIN_DISTANCE: f32 = 0.2;
+
+const MIN_FOVY: f32 = 5.0;
+const MAX_FOVY: f32 = 150.0;
+const MAX_DISTANCE: f32 = 500.0;
+
 impl Camera {
     pub fn new(
         position: Vector3<f32>,
@@ -31,38 +122,204 @@ impl Camera {
         fovy: cgmath::Deg<f32>,
         dimensions: (u32, u32),
     ) -> Self {
-        let front = (position - target).normalize();
-        let right = cgmath::vec3(0.0, 1.0, 0.0).cross(front).normalize();
-        let up = front.cross(right);
+        let front = (target - position).normalize();
 
-        let front = -front;
-
-        let pitch = front.y.asin();
-        let yaw = (front.x / pitch.cos()).acos();
+        // Inverse of the front vector computation in `rotate`
+        let pitch = (-front.y).asin();
+        let yaw = (-front.z).atan2(-front.x);
         Self {
             position,
             front,
-            up,
             yaw: cgmath::Rad(yaw),
             pitch: cgmath::Rad(pitch),
+            mode: CameraMode::default(),
+            target,
+            distance: (target - position).magnitude(),
+            projection_type: ProjectionType::default(),
             fovy,
+            z_near: Z_NEAR,
+            z_far: Some(Z_FAR),
+            reversed_depth: false,
             dimensions,
             aspect: dimensions.0 as f32 / dimensions.1 as f32,
             framebuffer: Framebuffer::render_buffer(dimensions.0, dimensions.1).unwrap(),
         }
     }
 
+    pub fn position(&self) -> Vector3<f32> {
+        self.position
+    }
+
+    pub fn set_position(&mut self, position: Vector3<f32>) {
+        self.position = position;
+    }
+
+    pub fn front(&self) -> Vector3<f32> {
+        self.front
+    }
+
+    pub fn pose(&self) -> CameraPose {
+        CameraPose {
+            position: self.position,
+            yaw: self.yaw.into(),
+            pitch: self.pitch.into(),
+            fovy: self.fovy,
+        }
+    }
+
+    /// Places the camera, orbiting continues around the point in front of the new pose
+    pub fn set_pose(&mut self, pose: CameraPose) {
+        self.position = pose.position;
+        self.yaw = pose.yaw.into();
+        self.pitch = cgmath::Rad(
+            cgmath::Rad::from(pose.pitch)
+                .0
+                .clamp(-PITCH_BOUND, PITCH_BOUND),
+        );
+        self.set_fovy(pose.fovy);
+        self.update_front();
+
+        self.target = self.position + self.front * self.distance;
+    }
+
+    pub fn mode(&self) -> CameraMode {
+        self.mode
+    }
+
+    /// Orbiting starts around the point in front of the camera at the last orbit distance
+    pub fn set_mode(&mut self, mode: CameraMode) {
+        if mode == CameraMode::Orbit && self.mode != CameraMode::Orbit {
+            self.target = self.position + self.front * self.distance;
+        }
+        self.mode = mode;
+    }
+
     pub fn view(&self) -> cgmath::Matrix4<f32> {
         let direction = self.position + self.front;
         cgmath::Matrix4::look_at_rh(
             cgmath::point3(self.position.x, self.position.y, self.position.z),
             cgmath::point3(direction.x, direction.y, direction.z),
-            self.up,
+            WORLD_UP,
         )
     }
 
+    /// View and projection from `pose` instead of the camera's own, e.g. between two updates.
+    /// The camera itself isn't changed.
+    pub fn pose_view_projection(
+        &self,
+        pose: CameraPose,
+    ) -> (cgmath::Matrix4<f32>, cgmath::Matrix4<f32>) {
+        let front = front_of(pose.yaw.into(), pose.pitch.into());
+        let direction = pose.position + front;
+        let view = cgmath::Matrix4::look_at_rh(
+            cgmath::point3(pose.position.x, pose.position.y, pose.position.z),
+            cgmath::point3(direction.x, direction.y, direction.z),
+            WORLD_UP,
+        );
+
+        (view, self.projection_for(pose.fovy))
+    }
+
     pub fn projection(&self) -> cgmath::Matrix4<f32> {
-        cgmath::perspective(self.fovy, self.aspect, Z_NEAR, Z_FAR)
+        self.projection_for(self.fovy)
+    }
+
+    fn projection_for(&self, fovy: cgmath::Deg<f32>) -> cgmath::Matrix4<f32> {
+        match self.projection_type {
+            ProjectionType::Perspective => self.projection_with_fovy(fovy.into()),
+            ProjectionType::Orthographic => {
+                let half_height = self.distance * (cgmath::Rad::from(fovy).0 * 0.5).tan();
+                let half_width = half_height * self.aspect;
+                let far = self.z_far.unwrap_or(ORTHOGRAPHIC_FAR);
+
+                self.reverse_depth(cgmath::ortho(
+                    -half_width,
+                    half_width,
+                    -half_height,
+                    half_height,
+                    self.z_near,
+                    far,
+                ))
+            }
+        }
+    }
+
+    /// Perspective projection of the camera's viewport and clip planes with another
+    /// field of view, e.g. of a fixed shot
+    pub fn projection_with_fovy(&self, fovy: cgmath::Rad<f32>) -> cgmath::Matrix4<f32> {
+        let projection = match self.z_far {
+            Some(far) => cgmath::perspective(fovy, self.aspect, self.z_near, far),
+            None => {
+                // Limit of the perspective matrix as far goes to infinity
+                let focal = 1.0 / (fovy.0 * 0.5).tan();
+                cgmath::Matrix4::new(
+                    focal / self.aspect,
+                    0.0,
+                    0.0,
+                    0.0,
+                    0.0,
+                    focal,
+                    0.0,
+                    0.0,
+                    0.0,
+                    0.0,
+                    -1.0,
+                    -1.0,
+                    0.0,
+                    0.0,
+                    -2.0 * self.z_near,
+                    0.0,
+                )
+            }
+        };
+
+        self.reverse_depth(projection)
+    }
+
+    /// Mirrors clip space depth so the near plane ends at depth 1 and the far plane at 0.
+    /// GL 3.3 maps depth from [-1, 1] (no glClipControl), so the float depth buffer
+    /// gains precision far away but not all of it.
+    fn reverse_depth(&self, projection: cgmath::Matrix4<f32>) -> cgmath::Matrix4<f32> {
+        if self.reversed_depth {
+            cgmath::Matrix4::from_nonuniform_scale(1.0, 1.0, -1.0) * projection
+        } else {
+            projection
+        }
+    }
+
+    pub fn projection_type(&self) -> ProjectionType {
+        self.projection_type
+    }
+
+    pub fn set_projection_type(&mut self, projection_type: ProjectionType) {
+        self.projection_type = projection_type;
+    }
+
+    pub fn fovy(&self) -> cgmath::Deg<f32> {
+        self.fovy
+    }
+
+    pub fn set_fovy(&mut self, fovy: cgmath::Deg<f32>) {
+        self.fovy = cgmath::Deg(fovy.0.clamp(MIN_FOVY, MAX_FOVY));
+    }
+
+    pub fn clip_planes(&self) -> (f32, Option<f32>) {
+        (self.z_near, self.z_far)
+    }
+
+    /// `far` of None is an infinite far plane, orthographic projections stop at 1000 then.
+    /// The near plane is kept in front of the camera and the far plane behind it.
+    pub fn set_clip_planes(&mut self, near: f32, far: Option<f32>) {
+        self.z_near = near.max(MIN_Z_NEAR);
+        self.z_far = far.map(|far| far.max(self.z_near + MIN_CLIP_DEPTH));
+    }
+
+    pub fn reversed_depth(&self) -> bool {
+        self.reversed_depth
+    }
+
+    pub fn set_reversed_depth(&mut self, reversed: bool) {
+        self.reversed_depth = reversed;
     }
 
     pub fn set_dimensions(&mut self, dimensions: (u32, u32)) {
@@ -71,6 +328,36 @@ impl Camera {
         self.framebuffer = Framebuffer::render_buffer(dimensions.0, dimensions.1).unwrap();
     }
 
+    /// Keeps the view direction and moves the camera so the bounds fill the view
+    pub fn frame(&mut self, bounds: Aabb) {
+        if bounds.is_empty() {
+            return;
+        }
+
+        // Fit the bounding sphere into the narrower of the two fields of view
+        let half_fovy = cgmath::Rad::from(self.fovy).0 * 0.5;
+        let half_fov = half_fovy.min((half_fovy.tan() * self.aspect).atan());
+
+        self.target = bounds.center();
+        self.distance = (bounds.radius() / half_fov.sin()).clamp(MIN_DISTANCE, MAX_DISTANCE);
+        self.position = self.target - self.front * self.distance;
+    }
+
+    /// Positive steps zoom in, flying moves forward instead
+    pub fn zoom(&mut self, steps: f32) {
+        match self.mode {
+            CameraMode::Fly | CameraMode::Fixed => {
+                self.position += MOVEMENT_SPEED * WHEEL_ZOOM * steps * self.front
+            }
+            CameraMode::Orbit => {
+                self.distance =
+                    (self.distance * (-steps * WHEEL_ZOOM).exp()).clamp(MIN_DISTANCE, MAX_DISTANCE);
+                self.position = self.target - self.front * self.distance;
+            }
+            CameraMode::Walk => {}
+        }
+    }
+
     pub fn update(
         &mut self,
         front: f32,
@@ -81,11 +368,30 @@ impl Camera {
         down: f32,
         mouse: (f32, f32),
     ) {
-        // TODO: Fix invalid movement when starting position is not (0, 0, 0)
-        self.position += MOVEMENT_SPEED * (front - back) * self.front;
-        self.position += MOVEMENT_SPEED * (right - left) * self.front.cross(self.up).normalize();
-        self.position += MOVEMENT_SPEED * (up - down) * self.up;
+        self.rotate(mouse);
+        let right_direction = self.front.cross(WORLD_UP).normalize();
+
+        match self.mode {
+            CameraMode::Fly | CameraMode::Fixed => {
+                self.position += MOVEMENT_SPEED * (front - back) * self.front;
+                self.position += MOVEMENT_SPEED * (right - left) * right_direction;
+                self.position += MOVEMENT_SPEED * (up - down) * WORLD_UP;
+            }
+            CameraMode::Orbit => {
+                let pan = PAN_SPEED * self.distance;
+                self.target += pan * (right - left) * right_direction;
+                self.target += pan * (up - down) * WORLD_UP;
+
+                self.distance = (self.distance * (-(front - back) * ZOOM_SPEED).exp())
+                    .clamp(MIN_DISTANCE, MAX_DISTANCE);
+                self.position = self.target - self.front * self.distance;
+            }
+            // Moved by the walker
+            CameraMode::Walk => {}
+        }
+    }
 
+    fn rotate(&mut self, mouse: (f32, f32)) {
         self.yaw += cgmath::Rad(mouse.0 * MOUSE_SENSIVITY);
         self.pitch += cgmath::Rad(mouse.1 * MOUSE_SENSIVITY);
 
@@ -99,11 +405,19 @@ impl Camera {
         self.pitch = cgmath::Rad(self.pitch.0.max(-PITCH_BOUND));
         self.pitch = cgmath::Rad(self.pitch.0.min(PITCH_BOUND));
 
-        self.front = -cgmath::vec3(
-            self.yaw.cos() * self.pitch.cos(),
-            self.pitch.sin(),
-            self.yaw.sin() * self.pitch.cos(),
-        )
-        .normalize();
+        self.update_front();
+    }
+
+    fn update_front(&mut self) {
+        self.front = front_of(self.yaw, self.pitch);
     }
 }
+
+fn front_of(yaw: cgmath::Rad<f32>, pitch: cgmath::Rad<f32>) -> Vector3<f32> {
+    -cgmath::vec3(
+        yaw.cos() * pitch.cos(),
+        pitch.sin(),
+        yaw.sin() * pitch.cos(),
+    )
+    .normalize()
+}
@@ -0,0 +1,347 @@
+use std::ops::{Add, Mul, Sub};
+use std::path::Path;
+
+use crate::camera::CameraPose;
+
+/// Time between recorded keyframes, the spline fills in the rest
+const RECORD_INTERVAL: f32 = 0.1;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Keyframe {
+    /// Seconds from the start of the path
+    pub time: f32,
+    pub pose: CameraPose,
+}
+
+/// Catmull-Rom spline through camera keyframes.
+///
+/// Stored as text, one keyframe per line: `time x y z yaw pitch fovy`
+/// (seconds, world units, degrees). Empty lines and lines starting with `#` are ignored.
+#[derive(Debug, Default, Clone)]
+pub struct CameraPath {
+    keyframes: Vec<Keyframe>,
+}
+
+impl CameraPath {
+    pub fn new(keyframes: impl IntoIterator<Item = Keyframe>) -> Self {
+        let mut keyframes: Vec<_> = keyframes.into_iter().collect();
+        keyframes.sort_by(|a, b| a.time.total_cmp(&b.time));
+
+        let mut path = Self::default();
+        for keyframe in keyframes {
+            path.push(keyframe);
+        }
+
+        path
+    }
+
+    /// Appends a keyframe, its yaw is unwrapped so the camera turns the short way
+    pub fn push(&mut self, mut keyframe: Keyframe) {
+        if let Some(last) = self.keyframes.last() {
+            let turn = (keyframe.pose.yaw - last.pose.yaw).0;
+            keyframe.pose.yaw.0 -= 360.0 * (turn / 360.0).round();
+        }
+
+        self.keyframes.push(keyframe);
+    }
+
+    pub fn keyframes(&self) -> &[Keyframe] {
+        &self.keyframes
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.keyframes.last().map_or(0.0, |last| last.time)
+    }
+
+    /// Pose at the time, clamped to the ends of the path
+    pub fn sample(&self, time: f32) -> Option<CameraPose> {
+        let (first, last) = (self.keyframes.first()?, self.keyframes.last()?);
+        if time <= first.time {
+            return Some(first.pose);
+        }
+        if time >= last.time {
+            return Some(last.pose);
+        }
+
+        // Keyframes of the segment and their neighbours, the ends are mirrored
+        let next = self
+            .keyframes
+            .partition_point(|keyframe| keyframe.time <= time);
+        let (k1, k2) = (self.keyframes[next - 1], self.keyframes[next]);
+        let k0 = self
+            .keyframes
+            .get(next.wrapping_sub(2))
+            .copied()
+            .unwrap_or_else(|| mirror(k1, k2));
+        let k3 = self
+            .keyframes
+            .get(next + 1)
+            .copied()
+            .unwrap_or_else(|| mirror(k2, k1));
+
+        let times = [k0.time, k1.time, k2.time, k3.time];
+        let spline = |value: fn(&Keyframe) -> f32| {
+            catmull_rom(
+                [value(&k0), value(&k1), value(&k2), value(&k3)],
+                times,
+                time,
+            )
+        };
+
+        Some(CameraPose {
+            position: catmull_rom(
+                [
+                    k0.pose.position,
+                    k1.pose.position,
+                    k2.pose.position,
+                    k3.pose.position,
+                ],
+                times,
+                time,
+            ),
+            yaw: cgmath::Deg(spline(|keyframe| keyframe.pose.yaw.0)),
+            pitch: cgmath::Deg(spline(|keyframe| keyframe.pose.pitch.0)),
+            fovy: cgmath::Deg(spline(|keyframe| keyframe.pose.fovy.0)),
+        })
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)
+            .map_err(|error| format!("{}: {error}", path.display()))?;
+
+        let mut keyframes = Vec::new();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let values: Vec<f32> = line
+                .split_whitespace()
+                .map(str::parse)
+                .collect::<Result<_, _>>()
+                .map_err(|error| format!("{}:{}: {error}", path.display(), i + 1))?;
+
+            let [time, x, y, z, yaw, pitch, fovy] = values[..] else {
+                return Err(format!(
+                    "{}:{}: expected `time x y z yaw pitch fovy`",
+                    path.display(),
+                    i + 1
+                ));
+            };
+
+            keyframes.push(Keyframe {
+                time,
+                pose: CameraPose {
+                    position: cgmath::vec3(x, y, z),
+                    yaw: cgmath::Deg(yaw),
+                    pitch: cgmath::Deg(pitch),
+                    fovy: cgmath::Deg(fovy),
+                },
+            });
+        }
+
+        Ok(Self::new(keyframes))
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        let mut text = String::from("# time x y z yaw pitch fovy\n");
+        for Keyframe { time, pose } in self.keyframes.iter() {
+            text.push_str(&format!(
+                "{time} {} {} {} {} {} {}\n",
+                pose.position.x,
+                pose.position.y,
+                pose.position.z,
+                pose.yaw.0,
+                pose.pitch.0,
+                pose.fovy.0,
+            ));
+        }
+
+        std::fs::write(path.as_ref(), text)
+            .map_err(|error| format!("{}: {error}", path.as_ref().display()))
+    }
+}
+
+/// Records camera poses at a fixed interval
+#[derive(Debug, Default)]
+pub struct Recorder {
+    path: CameraPath,
+    time: f32,
+    last_keyframe: Option<f32>,
+}
+
+impl Recorder {
+    pub fn update(&mut self, pose: CameraPose, delta: f32) {
+        let due = self
+            .last_keyframe
+            .is_none_or(|last| self.time - last >= RECORD_INTERVAL);
+        if due {
+            self.path.push(Keyframe {
+                time: self.time,
+                pose,
+            });
+            self.last_keyframe = Some(self.time);
+        }
+
+        self.time += delta;
+    }
+
+    pub fn finish(self) -> CameraPath {
+        self.path
+    }
+}
+
+/// Plays a path back, optionally looping
+#[derive(Debug)]
+pub struct Playback {
+    path: CameraPath,
+    time: f32,
+    looping: bool,
+}
+
+impl Playback {
+    pub fn new(path: CameraPath, looping: bool) -> Self {
+        Self {
+            path,
+            time: 0.0,
+            looping,
+        }
+    }
+
+    /// Advances the playback, returns None once a non looping path has ended
+    pub fn advance(&mut self, delta: f32) -> Option<CameraPose> {
+        let duration = self.path.duration();
+        if self.time > duration {
+            if !self.looping {
+                return None;
+            }
+            self.time = if duration > 0.0 {
+                self.time % duration
+            } else {
+                0.0
+            };
+        }
+
+        let pose = self.path.sample(self.time);
+        self.time += delta;
+
+        pose
+    }
+}
+
+/// Keyframe reflected over `origin`, so the end segments have a sensible tangent
+fn mirror(origin: Keyframe, other: Keyframe) -> Keyframe {
+    let reflect = |origin: f32, other: f32| 2.0 * origin - other;
+
+    Keyframe {
+        time: reflect(origin.time, other.time),
+        pose: CameraPose {
+            position: origin.pose.position * 2.0 - other.pose.position,
+            yaw: cgmath::Deg(reflect(origin.pose.yaw.0, other.pose.yaw.0)),
+            pitch: cgmath::Deg(reflect(origin.pose.pitch.0, other.pose.pitch.0)),
+            fovy: cgmath::Deg(reflect(origin.pose.fovy.0, other.pose.fovy.0)),
+        },
+    }
+}
+
+/// Catmull-Rom between `points[1]` and `points[2]` with non uniform keyframe times,
+/// evaluated as a cubic Hermite curve
+fn catmull_rom<T>(points: [T; 4], times: [f32; 4], time: f32) -> T
+where
+    T: Copy + Add<Output = T> + Sub<Output = T> + Mul<f32, Output = T>,
+{
+    let [p0, p1, p2, p3] = points;
+    let [t0, t1, t2, t3] = times;
+    let span = t2 - t1;
+
+    let m1 = (p2 - p0) * (span / (t2 - t0));
+    let m2 = (p3 - p1) * (span / (t3 - t1));
+
+    let t = (time - t1) / span;
+    let (t2, t3) = (t * t, t * t * t);
+
+    p1 * (2.0 * t3 - 3.0 * t2 + 1.0)
+        + m1 * (t3 - 2.0 * t2 + t)
+        + p2 * (-2.0 * t3 + 3.0 * t2)
+        + m2 * (t3 - t2)
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::InnerSpace;
+
+    use super::*;
+
+    fn keyframe(time: f32, x: f32, yaw: f32) -> Keyframe {
+        Keyframe {
+            time,
+            pose: CameraPose {
+                position: cgmath::vec3(x, 0.0, 0.0),
+                yaw: cgmath::Deg(yaw),
+                pitch: cgmath::Deg(0.0),
+                fovy: cgmath::Deg(60.0),
+            },
+        }
+    }
+
+    #[test]
+    fn passes_through_keyframes() {
+        let path = CameraPath::new([
+            keyframe(0.0, 0.0, 0.0),
+            keyframe(1.0, 3.0, 10.0),
+            keyframe(3.0, -2.0, 45.0),
+        ]);
+
+        for keyframe in path.keyframes() {
+            let pose = path.sample(keyframe.time).unwrap();
+            assert!((pose.position - keyframe.pose.position).magnitude() < 1e-5);
+            assert!((pose.yaw.0 - keyframe.pose.yaw.0).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn clamps_to_the_ends() {
+        let path = CameraPath::new([keyframe(1.0, 1.0, 0.0), keyframe(2.0, 5.0, 0.0)]);
+
+        assert_eq!(path.sample(0.0).unwrap().position.x, 1.0);
+        assert_eq!(path.sample(9.0).unwrap().position.x, 5.0);
+        assert!(CameraPath::default().sample(0.0).is_none());
+    }
+
+    #[test]
+    fn uniform_straight_motion_stays_linear() {
+        // Mirrored end keyframes keep the end segments straight too
+        let path = CameraPath::new((0..4).map(|i| keyframe(i as f32, 2.0 * i as f32, 0.0)));
+
+        for time in [0.25, 0.5, 1.5, 2.75] {
+            let x = path.sample(time).unwrap().position.x;
+            assert!((x - 2.0 * time).abs() < 1e-5, "{x} at {time}");
+        }
+    }
+
+    #[test]
+    fn yaw_turns_the_short_way() {
+        let path = CameraPath::new([keyframe(0.0, 0.0, 350.0), keyframe(1.0, 0.0, 10.0)]);
+
+        assert_eq!(path.keyframes()[1].pose.yaw.0, 370.0);
+        let yaw = path.sample(0.5).unwrap().yaw.0;
+        assert!((yaw - 360.0).abs() < 1e-4, "{yaw}");
+    }
+
+    #[test]
+    fn playback_ends_unless_looping() {
+        let path = CameraPath::new([keyframe(0.0, 0.0, 0.0), keyframe(1.0, 1.0, 0.0)]);
+
+        let mut once = Playback::new(path.clone(), false);
+        assert!(once.advance(0.6).is_some());
+        assert!(once.advance(0.6).is_some());
+        assert!(once.advance(0.6).is_none());
+
+        let mut looping = Playback::new(path, true);
+        for _ in 0..10 {
+            assert!(looping.advance(0.6).is_some());
+        }
+    }
+}
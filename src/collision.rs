@@ -0,0 +1,418 @@
+use std::collections::HashMap;
+
+use crate::bounds::Aabb;
+use crate::camera::WORLD_UP;
+
+use cgmath::prelude::*;
+use cgmath::Vector3;
+
+/// Edge of the grid cells triangles are sorted into
+const CELL_SIZE: f32 = 2.0;
+/// Collision passes per movement step
+const RESOLVE_ITERATIONS: usize = 4;
+/// Contacts with a normal steeper than ~50° don't count as ground
+const MIN_GROUND_NORMAL_Y: f32 = 0.65;
+/// Contact normals this close to the face normal are snapped to it
+const COPLANAR_EDGE_DOT: f32 = 0.995;
+const EPSILON: f32 = 1e-6;
+
+/// Vertical capsule standing on `feet`
+#[derive(Debug, Clone, Copy)]
+pub struct Capsule {
+    pub radius: f32,
+    /// Total height including both caps
+    pub height: f32,
+}
+
+impl Capsule {
+    /// Centers of the bottom and top cap
+    fn segment(&self, feet: Vector3<f32>) -> (Vector3<f32>, Vector3<f32>) {
+        (
+            feet + WORLD_UP * self.radius,
+            feet + WORLD_UP * (self.height - self.radius),
+        )
+    }
+
+    fn bounds(&self, feet: Vector3<f32>) -> Aabb {
+        let radius = cgmath::vec3(self.radius, self.radius, self.radius);
+        let (base, tip) = self.segment(feet);
+
+        Aabb::from_points(&[base - radius, tip + radius])
+    }
+}
+
+/// Result of moving a capsule through the scene
+#[derive(Debug, Clone, Copy)]
+pub struct Slide {
+    pub feet: Vector3<f32>,
+    /// Touched walkable ground
+    pub grounded: bool,
+    /// Touched something from below
+    pub ceiling: bool,
+}
+
+/// World space triangles of the scene, sorted into a uniform grid
+#[derive(Debug, Default)]
+pub struct CollisionMesh {
+    triangles: Vec<[Vector3<f32>; 3]>,
+    cells: HashMap<(i32, i32, i32), Vec<u32>>,
+}
+
+impl CollisionMesh {
+    pub fn new(triangles: impl IntoIterator<Item = [Vector3<f32>; 3]>) -> Self {
+        let mut mesh = Self::default();
+
+        for triangle in triangles {
+            let normal = (triangle[1] - triangle[0]).cross(triangle[2] - triangle[0]);
+            if normal.magnitude2() <= EPSILON * EPSILON {
+                continue;
+            }
+
+            let index = mesh.triangles.len() as u32;
+            mesh.triangles.push(triangle);
+
+            for cell in cells(Aabb::from_points(&triangle)) {
+                mesh.cells.entry(cell).or_default().push(index);
+            }
+        }
+
+        mesh
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.triangles.len()
+    }
+
+    /// Moves the capsule by `displacement`, sliding along everything it touches.
+    /// Long moves are split so the capsule can't tunnel through thin walls.
+    pub fn slide(&self, capsule: Capsule, feet: Vector3<f32>, displacement: Vector3<f32>) -> Slide {
+        let max_step = capsule.radius * 0.5;
+        let steps = (displacement.magnitude() / max_step).ceil().max(1.0);
+        let step = displacement / steps;
+
+        let mut slide = Slide {
+            feet,
+            grounded: false,
+            ceiling: false,
+        };
+        for _ in 0..steps as usize {
+            slide.feet += step;
+            self.resolve(capsule, &mut slide);
+        }
+
+        slide
+    }
+
+    /// Pushes the capsule out of intersecting triangles
+    fn resolve(&self, capsule: Capsule, slide: &mut Slide) {
+        let mut candidates: Vec<u32> = cells(capsule.bounds(slide.feet))
+            .filter_map(|cell| self.cells.get(&cell))
+            .flatten()
+            .copied()
+            .collect();
+        candidates.sort_unstable();
+        candidates.dedup();
+
+        for _ in 0..RESOLVE_ITERATIONS {
+            // Ground first: a step's rim lifts the capsule before its front face pushes it back
+            let grounded = self.resolve_pass(capsule, slide, &candidates, true);
+            let pushed = self.resolve_pass(capsule, slide, &candidates, false);
+
+            if !grounded && !pushed {
+                break;
+            }
+        }
+    }
+
+    /// Resolves either ground or other contacts, returns whether any was found
+    fn resolve_pass(
+        &self,
+        capsule: Capsule,
+        slide: &mut Slide,
+        candidates: &[u32],
+        ground: bool,
+    ) -> bool {
+        let mut found = false;
+
+        for &triangle in candidates {
+            let (base, tip) = capsule.segment(slide.feet);
+            let Some(contact) =
+                capsule_contact(base, tip, capsule.radius, self.triangles[triangle as usize])
+            else {
+                continue;
+            };
+
+            // Walkable surfaces under the bottom cap, including the rims of steps, push
+            // straight up so standing on slopes and edges doesn't slide
+            let offset = contact.center - contact.point;
+            let is_ground =
+                contact.point.y <= base.y && contact.face_normal.y >= MIN_GROUND_NORMAL_Y;
+            if is_ground != ground {
+                continue;
+            }
+
+            if is_ground {
+                let horizontal = offset.x * offset.x + offset.z * offset.z;
+                slide.feet.y += (capsule.radius * capsule.radius - horizontal)
+                    .max(0.0)
+                    .sqrt()
+                    - offset.y;
+                slide.grounded = true;
+            } else {
+                slide.feet += contact.normal * contact.depth;
+                slide.ceiling |= contact.normal.y <= -MIN_GROUND_NORMAL_Y;
+            }
+            found = true;
+        }
+
+        found
+    }
+}
+
+fn cell(point: Vector3<f32>) -> (i32, i32, i32) {
+    (
+        (point.x / CELL_SIZE).floor() as i32,
+        (point.y / CELL_SIZE).floor() as i32,
+        (point.z / CELL_SIZE).floor() as i32,
+    )
+}
+
+fn cells(bounds: Aabb) -> impl Iterator<Item = (i32, i32, i32)> {
+    let (min, max) = (cell(bounds.min), cell(bounds.max));
+
+    (min.0..=max.0).flat_map(move |x| {
+        (min.1..=max.1).flat_map(move |y| (min.2..=max.2).map(move |z| (x, y, z)))
+    })
+}
+
+struct Contact {
+    /// Center of the capsule sphere touching the triangle
+    center: Vector3<f32>,
+    /// Closest point of the triangle
+    point: Vector3<f32>,
+    /// Push out direction and distance
+    normal: Vector3<f32>,
+    depth: f32,
+    /// Triangle normal facing the capsule
+    face_normal: Vector3<f32>,
+}
+
+/// Contact of a capsule (segment `base`-`tip` swept by `radius`) and a triangle
+fn capsule_contact(
+    base: Vector3<f32>,
+    tip: Vector3<f32>,
+    radius: f32,
+    [a, b, c]: [Vector3<f32>; 3],
+) -> Option<Contact> {
+    let plane_normal = (b - a).cross(c - a).normalize();
+
+    // Point of the triangle closest to the capsule axis, found through the
+    // intersection of the axis with the triangle's plane
+    let axis = tip - base;
+    let denominator = plane_normal.dot(axis);
+    let on_plane = if denominator.abs() > EPSILON {
+        base + axis * (plane_normal.dot(a - base) / denominator)
+    } else {
+        base
+    };
+    let reference = closest_on_triangle(on_plane, a, b, c);
+
+    let center = closest_on_segment(reference, base, tip);
+    let point = closest_on_triangle(center, a, b, c);
+    let distance = (center - point).magnitude();
+    if distance >= radius {
+        return None;
+    }
+
+    let face_normal = if plane_normal.dot(center - a) >= 0.0 {
+        plane_normal
+    } else {
+        -plane_normal
+    };
+    // Edges shared by coplanar triangles give almost but not exactly the face normal,
+    // which makes the capsule drift along flat walls
+    let normal = (center - point) / distance.max(EPSILON);
+    let normal = if distance <= EPSILON || normal.dot(face_normal) >= COPLANAR_EDGE_DOT {
+        face_normal
+    } else {
+        normal
+    };
+
+    Some(Contact {
+        center,
+        point,
+        normal,
+        depth: radius - distance,
+        face_normal,
+    })
+}
+
+fn closest_on_segment(point: Vector3<f32>, a: Vector3<f32>, b: Vector3<f32>) -> Vector3<f32> {
+    let ab = b - a;
+    let t = (point - a).dot(ab) / ab.magnitude2().max(EPSILON);
+
+    a + ab * t.clamp(0.0, 1.0)
+}
+
+/// Real-Time Collision Detection (Ericson), 5.1.5
+fn closest_on_triangle(
+    point: Vector3<f32>,
+    a: Vector3<f32>,
+    b: Vector3<f32>,
+    c: Vector3<f32>,
+) -> Vector3<f32> {
+    let (ab, ac, ap) = (b - a, c - a, point - a);
+    let (d1, d2) = (ab.dot(ap), ac.dot(ap));
+    if d1 <= 0.0 && d2 <= 0.0 {
+        return a;
+    }
+
+    let bp = point - b;
+    let (d3, d4) = (ab.dot(bp), ac.dot(bp));
+    if d3 >= 0.0 && d4 <= d3 {
+        return b;
+    }
+
+    let vc = d1 * d4 - d3 * d2;
+    if vc <= 0.0 && d1 >= 0.0 && d3 <= 0.0 {
+        return a + ab * (d1 / (d1 - d3));
+    }
+
+    let cp = point - c;
+    let (d5, d6) = (ab.dot(cp), ac.dot(cp));
+    if d6 >= 0.0 && d5 <= d6 {
+        return c;
+    }
+
+    let vb = d5 * d2 - d1 * d6;
+    if vb <= 0.0 && d2 >= 0.0 && d6 <= 0.0 {
+        return a + ac * (d2 / (d2 - d6));
+    }
+
+    let va = d3 * d6 - d5 * d4;
+    if va <= 0.0 && (d4 - d3) >= 0.0 && (d5 - d6) >= 0.0 {
+        return b + (c - b) * ((d4 - d3) / ((d4 - d3) + (d5 - d6)));
+    }
+
+    let denominator = 1.0 / (va + vb + vc);
+    a + ab * (vb * denominator) + ac * (vc * denominator)
+}
+
+/// Level geometry shared by the collision and walking tests, 20 units deep along Z
+#[cfg(test)]
+pub(crate) mod fixtures {
+    use std::ops::Range;
+
+    use cgmath::Vector3;
+
+    pub type Quad = [[Vector3<f32>; 3]; 2];
+
+    /// Two triangles of the quad `a b c d`
+    pub fn quad([a, b, c, d]: [[f32; 3]; 4]) -> Quad {
+        let [a, b, c, d] = [a, b, c, d].map(Vector3::from);
+        [[a, b, c], [a, c, d]]
+    }
+
+    /// Floor at height `y` spanning `x`
+    pub fn floor(y: f32, x: Range<f32>) -> Quad {
+        quad([
+            [x.start, y, -10.0],
+            [x.end, y, -10.0],
+            [x.end, y, 10.0],
+            [x.start, y, 10.0],
+        ])
+    }
+
+    /// Wall in the plane `x` spanning the heights `y`
+    pub fn wall(x: f32, y: Range<f32>) -> Quad {
+        quad([
+            [x, y.start, -10.0],
+            [x, y.end, -10.0],
+            [x, y.end, 10.0],
+            [x, y.start, 10.0],
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fixtures::{floor, wall};
+    use super::*;
+
+    const CAPSULE: Capsule = Capsule {
+        radius: 0.5,
+        height: 2.0,
+    };
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-3
+    }
+
+    #[test]
+    fn closest_points_on_triangle() {
+        let (a, b, c) = (
+            cgmath::vec3(0.0, 0.0, 0.0),
+            cgmath::vec3(2.0, 0.0, 0.0),
+            cgmath::vec3(0.0, 0.0, 2.0),
+        );
+
+        let inside = closest_on_triangle(cgmath::vec3(0.5, 3.0, 0.5), a, b, c);
+        assert_eq!(inside, cgmath::vec3(0.5, 0.0, 0.5));
+        let vertex = closest_on_triangle(cgmath::vec3(-1.0, 1.0, -1.0), a, b, c);
+        assert_eq!(vertex, a);
+        let edge = closest_on_triangle(cgmath::vec3(1.0, 0.0, -1.0), a, b, c);
+        assert_eq!(edge, cgmath::vec3(1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn lands_on_the_floor() {
+        let mesh = CollisionMesh::new(floor(0.0, -10.0..10.0));
+        let slide = mesh.slide(
+            CAPSULE,
+            cgmath::vec3(0.0, 1.0, 0.0),
+            cgmath::vec3(0.0, -2.0, 0.0),
+        );
+
+        assert!(slide.grounded);
+        assert!(!slide.ceiling);
+        assert!(close(slide.feet.y, 0.0), "{:?}", slide.feet);
+    }
+
+    #[test]
+    fn slides_along_walls() {
+        let mesh = CollisionMesh::new(
+            floor(0.0, -10.0..10.0)
+                .into_iter()
+                .chain(wall(1.0, 0.0..5.0)),
+        );
+        let start = cgmath::vec3(0.0, 0.0, 0.0);
+        let slide = mesh.slide(CAPSULE, start, cgmath::vec3(2.0, 0.0, 2.0));
+
+        // Stopped by the wall, but the move along it is kept
+        assert!(
+            close(slide.feet.x, 1.0 - CAPSULE.radius),
+            "{:?}",
+            slide.feet
+        );
+        assert!(close(slide.feet.z, 2.0), "{:?}", slide.feet);
+        assert!(close(slide.feet.y, 0.0), "{:?}", slide.feet);
+    }
+
+    #[test]
+    fn hits_the_ceiling() {
+        let mesh = CollisionMesh::new(floor(3.0, -10.0..10.0));
+        let slide = mesh.slide(
+            CAPSULE,
+            cgmath::vec3(0.0, 0.0, 0.0),
+            cgmath::vec3(0.0, 2.0, 0.0),
+        );
+
+        assert!(slide.ceiling);
+        assert!(!slide.grounded);
+        assert!(
+            close(slide.feet.y, 3.0 - CAPSULE.height),
+            "{:?}",
+            slide.feet
+        );
+    }
+}
@@ -0,0 +1,140 @@
+use crate::bounds::Aabb;
+use crate::camera::WORLD_UP;
+
+use cgmath::prelude::*;
+use cgmath::Vector3;
+
+/// Points this far outside of a convex volume still count as inside
+const CONTAINS_EPSILON: f32 = 1e-4;
+/// Tracked points closer to a tracking shot don't turn it, their direction is unstable
+const MIN_TRACKING_DISTANCE: f32 = 0.01;
+
+/// Pre-placed camera of the scene (glTF camera node)
+#[derive(Debug, Clone)]
+pub struct Shot {
+    pub name: String,
+    pub position: Vector3<f32>,
+    pub front: Vector3<f32>,
+    pub fovy: cgmath::Rad<f32>,
+    /// Pan-follow shot, keeps its position and turns towards the tracked point
+    pub tracking: bool,
+}
+
+impl Shot {
+    pub fn front_towards(&self, tracked: Vector3<f32>) -> Vector3<f32> {
+        let direction = tracked - self.position;
+        if self.tracking && direction.magnitude2() > MIN_TRACKING_DISTANCE * MIN_TRACKING_DISTANCE {
+            direction.normalize()
+        } else {
+            self.front
+        }
+    }
+
+    pub fn view(&self, tracked: Vector3<f32>) -> cgmath::Matrix4<f32> {
+        let target = self.position + self.front_towards(tracked);
+        cgmath::Matrix4::look_at_rh(
+            cgmath::point3(self.position.x, self.position.y, self.position.z),
+            cgmath::point3(target.x, target.y, target.z),
+            WORLD_UP,
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum TriggerVolume {
+    Aabb(Aabb),
+    /// Planes as (outward normal, distance from origin), inside is behind all of them
+    Convex(Vec<(Vector3<f32>, f32)>),
+}
+
+impl TriggerVolume {
+    /// Planes of counter-clockwise triangles of a closed convex mesh
+    pub fn convex(triangles: impl IntoIterator<Item = [Vector3<f32>; 3]>) -> Self {
+        let planes = triangles
+            .into_iter()
+            .filter_map(|[a, b, c]| {
+                let normal = (b - a).cross(c - a);
+                // Degenerate triangles don't define a plane
+                (normal.magnitude2() > f32::EPSILON).then(|| {
+                    let normal = normal.normalize();
+                    (normal, normal.dot(a))
+                })
+            })
+            .collect();
+
+        Self::Convex(planes)
+    }
+
+    pub fn contains(&self, point: Vector3<f32>) -> bool {
+        match self {
+            Self::Aabb(aabb) => {
+                (0..3).all(|axis| aabb.min[axis] <= point[axis] && point[axis] <= aabb.max[axis])
+            }
+            Self::Convex(planes) => planes
+                .iter()
+                .all(|(normal, distance)| normal.dot(point) - distance <= CONTAINS_EPSILON),
+        }
+    }
+}
+
+/// Cuts to `shot` when the tracked point enters `volume`
+#[derive(Debug, Clone)]
+pub struct Trigger {
+    pub shot: usize,
+    pub volume: TriggerVolume,
+}
+
+/// Survival horror styled static cameras switched by trigger volumes
+#[derive(Debug, Default)]
+pub struct FixedCameras {
+    shots: Vec<Shot>,
+    triggers: Vec<Trigger>,
+    active: Option<usize>,
+}
+
+impl FixedCameras {
+    /// The first shot is active until the tracked point enters a volume
+    pub fn new(shots: Vec<Shot>, triggers: Vec<Trigger>) -> Self {
+        let active = (!shots.is_empty()).then_some(0);
+
+        Self {
+            shots,
+            triggers,
+            active,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.shots.is_empty()
+    }
+
+    pub fn active_shot(&self) -> Option<&Shot> {
+        self.active.map(|shot| &self.shots[shot])
+    }
+
+    /// Cuts to the shot of a volume containing the point, returns true on a cut.
+    /// The active shot is kept while one of its own volumes contains the point
+    /// (so overlapping volumes don't flicker) and after leaving all volumes.
+    pub fn update(&mut self, point: Vector3<f32>) -> bool {
+        let contains = |trigger: &&Trigger| trigger.volume.contains(point);
+
+        if let Some(active) = self.active {
+            let inside_active = self
+                .triggers
+                .iter()
+                .filter(|trigger| trigger.shot == active)
+                .any(|trigger| contains(&trigger));
+            if inside_active {
+                return false;
+            }
+        }
+
+        match self.triggers.iter().find(contains) {
+            Some(trigger) if Some(trigger.shot) != self.active => {
+                self.active = Some(trigger.shot);
+                true
+            }
+            _ => false,
+        }
+    }
+}
@@ -1,72 +1,273 @@
-use crate::{texture::Texture2D, GlObject};
+use crate::leak::{self, GlKind};
+use crate::texture::{PixelFormat, Texture2D};
+use crate::GlObject;
+
+use gl::types::GLenum;
+
+pub const COLOR_RGBA5551: PixelFormat =
+    PixelFormat::new(gl::RGBA, gl::RGBA, gl::UNSIGNED_SHORT_5_5_5_1);
+pub const DEPTH_F32: PixelFormat =
+    PixelFormat::new(gl::DEPTH_COMPONENT32F, gl::DEPTH_COMPONENT, gl::FLOAT);
+pub const DEPTH24_STENCIL8: PixelFormat = PixelFormat::new(
+    gl::DEPTH24_STENCIL8,
+    gl::DEPTH_STENCIL,
+    gl::UNSIGNED_INT_24_8,
+);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FramebufferError {
+    InvalidDimensions(u32, u32),
+    TooManyColorAttachments {
+        requested: usize,
+        supported: usize,
+    },
+    /// Status returned by `glCheckFramebufferStatus`
+    Incomplete(GLenum),
+    /// Index of a color attachment the framebuffer doesn't have
+    MissingColorAttachment(u32),
+}
+
+impl std::fmt::Display for FramebufferError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FramebufferError::InvalidDimensions(width, height) => {
+                write!(f, "invalid framebuffer dimensions {width}x{height}")
+            }
+            FramebufferError::TooManyColorAttachments {
+                requested,
+                supported,
+            } => write!(
+                f,
+                "{requested} color attachments requested, only {supported} are supported"
+            ),
+            FramebufferError::Incomplete(status) => {
+                let reason = match *status {
+                    gl::FRAMEBUFFER_UNDEFINED => "default framebuffer does not exist",
+                    gl::FRAMEBUFFER_INCOMPLETE_ATTACHMENT => "an attachment is incomplete",
+                    gl::FRAMEBUFFER_INCOMPLETE_MISSING_ATTACHMENT => "no image is attached",
+                    gl::FRAMEBUFFER_INCOMPLETE_DRAW_BUFFER => "a draw buffer has no attachment",
+                    gl::FRAMEBUFFER_INCOMPLETE_READ_BUFFER => "the read buffer has no attachment",
+                    gl::FRAMEBUFFER_UNSUPPORTED => {
+                        "combination of attachment formats is not supported"
+                    }
+                    gl::FRAMEBUFFER_INCOMPLETE_MULTISAMPLE => {
+                        "attachments have different sample counts"
+                    }
+                    gl::FRAMEBUFFER_INCOMPLETE_LAYER_TARGETS => {
+                        "attachments are not layered in the same way"
+                    }
+                    _ => "unknown status",
+                };
+
+                write!(f, "framebuffer is incomplete: {reason} ({status:#x})")
+            }
+            FramebufferError::MissingColorAttachment(index) => {
+                write!(f, "framebuffer has no color attachment {index}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for FramebufferError {}
+
+#[derive(Debug)]
+pub struct FramebufferBuilder {
+    dimensions: (u32, u32),
+    color: Vec<PixelFormat>,
+    depth: Option<(GLenum, PixelFormat)>,
+}
+
+impl FramebufferBuilder {
+    pub fn color_attachment(mut self, format: PixelFormat) -> Self {
+        self.color.push(format);
+        self
+    }
+
+    pub fn depth_attachment(mut self, format: PixelFormat) -> Self {
+        self.depth = Some((gl::DEPTH_ATTACHMENT, format));
+        self
+    }
+
+    pub fn depth_stencil_attachment(mut self, format: PixelFormat) -> Self {
+        self.depth = Some((gl::DEPTH_STENCIL_ATTACHMENT, format));
+        self
+    }
+
+    pub fn build(self) -> Result<Framebuffer, FramebufferError> {
+        let (width, height) = self.dimensions;
+        if width == 0 || height == 0 {
+            return Err(FramebufferError::InvalidDimensions(width, height));
+        }
+
+        let supported = unsafe {
+            let mut max_attachments = 0;
+            gl::GetIntegerv(gl::MAX_COLOR_ATTACHMENTS, &mut max_attachments);
+
+            max_attachments as usize
+        };
+        if self.color.len() > supported {
+            return Err(FramebufferError::TooManyColorAttachments {
+                requested: self.color.len(),
+                supported,
+            });
+        }
+
+        let mut fbo = Framebuffer::new();
+        fbo.dimensions = self.dimensions;
+        fbo.color_attachments = self
+            .color
+            .iter()
+            .map(|format| attachment_texture(*format, self.dimensions))
+            .collect();
+        fbo.depth_attachment = self
+            .depth
+            .map(|(_, format)| attachment_texture(format, self.dimensions));
+
+        let status = fbo.as_context(|| unsafe {
+            let mut draw_buffers = Vec::with_capacity(fbo.color_attachments.len());
+            for (i, texture) in fbo.color_attachments.iter().enumerate() {
+                let attachment = gl::COLOR_ATTACHMENT0 + i as u32;
+                gl::FramebufferTexture2D(
+                    gl::FRAMEBUFFER,
+                    attachment,
+                    gl::TEXTURE_2D,
+                    texture.glid(),
+                    0,
+                );
+                draw_buffers.push(attachment);
+            }
+
+            if draw_buffers.is_empty() {
+                gl::DrawBuffer(gl::NONE);
+                gl::ReadBuffer(gl::NONE);
+            } else {
+                gl::DrawBuffers(draw_buffers.len() as i32, draw_buffers.as_ptr());
+            }
+
+            if let (Some((attachment, _)), Some(texture)) =
+                (self.depth, fbo.depth_attachment.as_ref())
+            {
+                gl::FramebufferTexture2D(
+                    gl::FRAMEBUFFER,
+                    attachment,
+                    gl::TEXTURE_2D,
+                    texture.glid(),
+                    0,
+                );
+            }
+
+            gl::CheckFramebufferStatus(gl::FRAMEBUFFER)
+        });
+
+        match status {
+            gl::FRAMEBUFFER_COMPLETE => Ok(fbo),
+            status => Err(FramebufferError::Incomplete(status)),
+        }
+    }
+}
+
+fn attachment_texture(format: PixelFormat, dimensions: (u32, u32)) -> Texture2D {
+    Texture2D::new(
+        gl::CLAMP_TO_EDGE,
+        gl::CLAMP_TO_EDGE,
+        gl::NEAREST,
+        gl::NEAREST,
+        None,
+        format,
+        dimensions,
+    )
+}
 
 #[derive(Debug)]
-#[repr(transparent)]
-pub(crate) struct Framebuffer(u32);
+pub struct Framebuffer {
+    id: u32,
+    dimensions: (u32, u32),
+    /// Textures attached to the framebuffer, deleted together with it
+    color_attachments: Vec<Texture2D>,
+    depth_attachment: Option<Texture2D>,
+}
 
 impl Framebuffer {
-    pub fn new() -> Self {
+    fn new() -> Self {
         unsafe {
             let mut fbo = 0;
             gl::GenFramebuffers(1, &mut fbo);
 
             assert_ne!(fbo, 0);
+            leak::created(GlKind::Framebuffer);
 
-            Self(fbo)
+            Self {
+                id: fbo,
+                dimensions: (0, 0),
+                color_attachments: Vec::new(),
+                depth_attachment: None,
+            }
         }
     }
 
-    pub fn render_buffer(width: u32, height: u32) -> Result<Self, ()> {
-        let mut fbo = Self::new();
-        let color_buffer = Texture2D::new(
-            gl::REPEAT,
-            gl::REPEAT,
-            gl::NEAREST,
-            gl::NEAREST,
-            None,
-            gl::RGBA,
-            gl::UNSIGNED_SHORT_5_5_5_1,
-            (width, height),
-        );
-
-        let depth_buffer = Texture2D::new(
-            gl::REPEAT,
-            gl::REPEAT,
-            gl::NEAREST,
-            gl::NEAREST,
-            None,
-            gl::DEPTH_COMPONENT,
-            gl::FLOAT,
-            (width, height),
-        );
-
-        fbo.as_context(|| unsafe {
-            gl::FramebufferTexture2D(
-                gl::FRAMEBUFFER,
-                gl::COLOR_ATTACHMENT0,
-                gl::TEXTURE_2D,
-                color_buffer.glid(),
-                0,
-            );
+    pub fn builder(width: u32, height: u32) -> FramebufferBuilder {
+        FramebufferBuilder {
+            dimensions: (width, height),
+            color: Vec::new(),
+            depth: None,
+        }
+    }
+
+    /// Low resolution PSX styled target: RGBA5551 color and float depth
+    pub fn render_buffer(width: u32, height: u32) -> Result<Self, FramebufferError> {
+        Self::builder(width, height)
+            .color_attachment(COLOR_RGBA5551)
+            .depth_attachment(DEPTH_F32)
+            .build()
+    }
+
+    pub fn dimensions(&self) -> (u32, u32) {
+        self.dimensions
+    }
+
+    /// Attached texture for sampling in later passes
+    pub fn color_attachment(&self, index: usize) -> Option<&Texture2D> {
+        self.color_attachments.get(index)
+    }
 
-            gl::FramebufferTexture2D(
-                gl::FRAMEBUFFER,
-                gl::DEPTH_ATTACHMENT,
-                gl::TEXTURE_2D,
-                depth_buffer.glid(),
+    pub fn depth_attachment(&self) -> Option<&Texture2D> {
+        self.depth_attachment.as_ref()
+    }
+
+    /// Color attachment as tightly packed RGBA8 rows, top row first
+    pub fn read_color(&self, index: u32) -> Result<Vec<u8>, FramebufferError> {
+        if index as usize >= self.color_attachments.len() {
+            return Err(FramebufferError::MissingColorAttachment(index));
+        }
+
+        let (width, height) = self.dimensions;
+        let row = width as usize * 4;
+        let mut pixels = vec![0u8; row * height as usize];
+
+        self.as_context(|| unsafe {
+            gl::ReadBuffer(gl::COLOR_ATTACHMENT0 + index);
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+            gl::ReadPixels(
+                0,
                 0,
+                width as i32,
+                height as i32,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                pixels.as_mut_ptr().cast(),
             );
-        })?;
+        });
 
-        Ok(fbo)
+        // GL rows start at the bottom
+        Ok(pixels.chunks_exact(row).rev().flatten().copied().collect())
     }
 
-    pub fn as_context<F, R>(&mut self, mut closure: F) -> Result<R, ()>
+    pub fn as_context<F, R>(&self, mut closure: F) -> R
     where
         F: FnMut() -> R,
     {
         unsafe {
-            gl::BindFramebuffer(gl::FRAMEBUFFER, self.0);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.id);
         }
 
         let result = closure();
@@ -75,20 +276,21 @@ impl Framebuffer {
             gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
         }
 
-        Ok(result)
+        result
     }
 }
 
 impl crate::GlObject for Framebuffer {
     fn glid(&self) -> gl::types::GLuint {
-        self.0
+        self.id
     }
 }
 
 impl Drop for Framebuffer {
     fn drop(&mut self) {
         unsafe {
-            gl::DeleteFramebuffers(1, &self.0);
+            gl::DeleteFramebuffers(1, &self.id);
         }
+        leak::deleted(GlKind::Framebuffer);
     }
 }
@@ -0,0 +1,322 @@
+use cgmath::{Vector3, Zero};
+
+use crate::bounds::Aabb;
+use crate::buffer::{Buffer, DrawType, Ebo, ObjectType, Vbo};
+use crate::mesh::Mesh;
+use crate::shader::vertex::{
+    COLOR_LOCATION, NORMAL_LOCATION, POSITION_LOCATION, TEXTURE_1_LOCATION, TEXTURE_LOCATION,
+};
+use crate::vao::Vao;
+
+/// Encoding of vertex attributes in the geometry buffer
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum VertexFormat {
+    /// 32 bit floats, 56 bytes per vertex
+    #[default]
+    Float,
+    /// PS1 styled integers, 32 bytes per vertex: 16 bit positions quantized over the
+    /// scene bounds, 8 bit normals and colors, float texture coordinates
+    Packed,
+}
+
+impl VertexFormat {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "float" => Some(VertexFormat::Float),
+            "packed" => Some(VertexFormat::Packed),
+            _ => None,
+        }
+    }
+
+    /// Bytes per vertex
+    fn stride(self) -> usize {
+        match self {
+            VertexFormat::Float => 56,
+            VertexFormat::Packed => 32,
+        }
+    }
+}
+
+/// Where a primitive's vertices and indices are in the geometry buffer
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct GeometryRange {
+    pub base_vertex: i32,
+    pub first_index: usize,
+    pub index_count: usize,
+}
+
+/// Vertices of every primitive interleaved in one buffer and their indices in another,
+/// drawn with base vertex offsets through a single vertex array
+#[derive(Debug)]
+pub(crate) struct GeometryBuffer {
+    vao: Vao,
+    _vbo: Vbo,
+    _ebo: Ebo,
+    format: VertexFormat,
+    /// Stored positions times scale plus offset give mesh space positions
+    position_scale: Vector3<f32>,
+    position_offset: Vector3<f32>,
+    vertex_count: usize,
+    size: usize,
+}
+
+impl GeometryBuffer {
+    /// Uploads the geometry of all primitives and assigns their ranges
+    pub fn new(meshes: &mut [Mesh], format: VertexFormat) -> Self {
+        let (position_scale, position_offset) = match format {
+            VertexFormat::Float => (cgmath::vec3(1.0, 1.0, 1.0), cgmath::vec3(0.0, 0.0, 0.0)),
+            VertexFormat::Packed => position_quantization(
+                meshes
+                    .iter()
+                    .map(Mesh::bounds)
+                    .fold(Aabb::empty(), Aabb::union),
+            ),
+        };
+
+        let mut vertices = Vec::new();
+        let mut indices: Vec<u32> = Vec::new();
+        let mut vertex_count = 0;
+        for primitive in meshes.iter_mut().flat_map(|mesh| mesh.0.iter_mut()) {
+            let data = primitive
+                .data()
+                .expect("Geometry is uploaded before CPU copies are released");
+
+            let range = GeometryRange {
+                base_vertex: vertex_count as i32,
+                first_index: indices.len(),
+                index_count: data.indices.len(),
+            };
+            indices.extend_from_slice(&data.indices);
+
+            for i in 0..data.vertices.len() {
+                let tex_coord = data.tex_coords.get(i).copied().unwrap_or_else(Zero::zero);
+                let tex_coord_1 = data
+                    .tex_coords_1
+                    .as_ref()
+                    .and_then(|tex_coords| tex_coords.get(i).copied())
+                    .unwrap_or_else(Zero::zero);
+                let color = data
+                    .colors
+                    .as_ref()
+                    .and_then(|colors| colors.get(i).copied())
+                    .unwrap_or(cgmath::vec4(1.0, 1.0, 1.0, 1.0));
+                let normal = data.normals.get(i).copied().unwrap_or_else(Zero::zero);
+                let position = data.vertices[i];
+
+                match format {
+                    VertexFormat::Float => {
+                        let floats = [
+                            position.x,
+                            position.y,
+                            position.z,
+                            normal.x,
+                            normal.y,
+                            normal.z,
+                            tex_coord.x,
+                            tex_coord.y,
+                            tex_coord_1.x,
+                            tex_coord_1.y,
+                            color.x,
+                            color.y,
+                            color.z,
+                            color.w,
+                        ];
+                        vertices.extend(floats.iter().flat_map(|float| float.to_ne_bytes()));
+                    }
+                    VertexFormat::Packed => {
+                        let [x, y, z] = quantize(position, position_scale, position_offset);
+                        for value in [x, y, z, 0] {
+                            vertices.extend(value.to_ne_bytes());
+                        }
+
+                        vertices.extend(
+                            [snorm(normal.x), snorm(normal.y), snorm(normal.z), 0]
+                                .map(|value| value as u8),
+                        );
+
+                        for value in [tex_coord.x, tex_coord.y, tex_coord_1.x, tex_coord_1.y] {
+                            vertices.extend(value.to_ne_bytes());
+                        }
+
+                        vertices.extend([
+                            unorm(color.x),
+                            unorm(color.y),
+                            unorm(color.z),
+                            unorm(color.w),
+                        ]);
+                    }
+                }
+            }
+            vertex_count += data.vertices.len();
+            primitive.range = range;
+        }
+
+        let mut vao = Vao::new();
+        let mut vbo = Vbo::new();
+        let mut ebo = Ebo::new();
+        let stride = format.stride();
+
+        vao.as_context(|| {
+            vbo.fill_with(&vertices, DrawType::Static);
+            ebo.fill_with(bytemuck::cast_slice(indices.as_slice()), DrawType::Static);
+
+            // (location, components, type, normalized, offset)
+            let attributes = match format {
+                VertexFormat::Float => [
+                    (POSITION_LOCATION, 3, ObjectType::Float, false, 0),
+                    (NORMAL_LOCATION, 3, ObjectType::Float, false, 12),
+                    (TEXTURE_LOCATION, 2, ObjectType::Float, false, 24),
+                    (TEXTURE_1_LOCATION, 2, ObjectType::Float, false, 32),
+                    (COLOR_LOCATION, 4, ObjectType::Float, false, 40),
+                ],
+                VertexFormat::Packed => [
+                    (POSITION_LOCATION, 3, ObjectType::Short, false, 0),
+                    (NORMAL_LOCATION, 3, ObjectType::Byte, true, 8),
+                    (TEXTURE_LOCATION, 2, ObjectType::Float, false, 12),
+                    (TEXTURE_1_LOCATION, 2, ObjectType::Float, false, 20),
+                    (COLOR_LOCATION, 4, ObjectType::UnsignedByte, true, 28),
+                ],
+            };
+            for (location, size, object_type, normalized, offset) in attributes {
+                vbo.set_interleaved_attrib_ptr(
+                    location,
+                    size,
+                    object_type,
+                    normalized,
+                    stride,
+                    offset,
+                );
+            }
+        });
+
+        Self {
+            vao,
+            _vbo: vbo,
+            _ebo: ebo,
+            format,
+            position_scale,
+            position_offset,
+            vertex_count,
+            size: vertices.len() + std::mem::size_of_val(indices.as_slice()),
+        }
+    }
+
+    pub fn bind(&self) {
+        self.vao.bind();
+    }
+
+    pub fn format(&self) -> VertexFormat {
+        self.format
+    }
+
+    /// Dequantization of stored positions, `position * scale + offset`
+    pub fn position_transform(&self) -> (Vector3<f32>, Vector3<f32>) {
+        (self.position_scale, self.position_offset)
+    }
+
+    pub fn vertex_count(&self) -> usize {
+        self.vertex_count
+    }
+
+    /// Bytes of vertex and index data
+    pub fn size(&self) -> usize {
+        self.size
+    }
+}
+
+/// Scale and offset mapping the bounds onto the full i16 range
+fn position_quantization(bounds: Aabb) -> (Vector3<f32>, Vector3<f32>) {
+    if bounds.is_empty() {
+        return (cgmath::vec3(1.0, 1.0, 1.0), cgmath::vec3(0.0, 0.0, 0.0));
+    }
+
+    let half_extent = (bounds.max - bounds.min) * 0.5;
+    let scale = half_extent.map(|extent| extent.max(f32::EPSILON) / i16::MAX as f32);
+    (scale, bounds.center())
+}
+
+fn quantize(position: Vector3<f32>, scale: Vector3<f32>, offset: Vector3<f32>) -> [i16; 3] {
+    (position - offset)
+        .zip(scale, |value, scale| (value / scale).round() as i16)
+        .into()
+}
+
+/// Normalized signed byte, -1 to 1
+fn snorm(value: f32) -> i8 {
+    (value.clamp(-1.0, 1.0) * 127.0).round() as i8
+}
+
+/// Normalized unsigned byte, 0 to 1
+fn unorm(value: f32) -> u8 {
+    (value.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{ElementWise, InnerSpace};
+
+    use super::*;
+
+    fn dequantize(quantized: [i16; 3], scale: Vector3<f32>, offset: Vector3<f32>) -> Vector3<f32> {
+        Vector3::from(quantized.map(f32::from)).mul_element_wise(scale) + offset
+    }
+
+    #[test]
+    fn positions_round_trip_within_half_a_step() {
+        let bounds = Aabb::from_points(&[
+            cgmath::vec3(-3.0, 0.5, 10.0),
+            cgmath::vec3(7.0, 0.75, 250.0),
+        ]);
+        let (scale, offset) = position_quantization(bounds);
+
+        let points = [
+            bounds.min,
+            bounds.max,
+            bounds.center(),
+            cgmath::vec3(1.2345, 0.6, 123.456),
+            cgmath::vec3(-2.999, 0.7499, 249.99),
+        ];
+        for point in points {
+            let decoded = dequantize(quantize(point, scale, offset), scale, offset);
+            let error = (decoded - point).map(f32::abs);
+
+            assert!(error.x <= scale.x * 0.5 + 1e-6, "{point:?} -> {decoded:?}");
+            assert!(error.y <= scale.y * 0.5 + 1e-6, "{point:?} -> {decoded:?}");
+            assert!(error.z <= scale.z * 0.5 + 1e-5, "{point:?} -> {decoded:?}");
+        }
+    }
+
+    #[test]
+    fn bounds_use_the_full_range() {
+        let bounds =
+            Aabb::from_points(&[cgmath::vec3(-1.0, -2.0, -4.0), cgmath::vec3(1.0, 2.0, 4.0)]);
+        let (scale, offset) = position_quantization(bounds);
+
+        assert_eq!(quantize(bounds.min, scale, offset), [-i16::MAX; 3]);
+        assert_eq!(quantize(bounds.max, scale, offset), [i16::MAX; 3]);
+    }
+
+    #[test]
+    fn flat_and_empty_bounds_stay_finite() {
+        let flat = Aabb::from_points(&[cgmath::vec3(0.0, 1.0, 0.0), cgmath::vec3(4.0, 1.0, 4.0)]);
+        let (scale, offset) = position_quantization(flat);
+        let point = cgmath::vec3(2.0, 1.0, 3.0);
+        assert!(
+            (dequantize(quantize(point, scale, offset), scale, offset) - point).magnitude() < 1e-3
+        );
+
+        assert_eq!(
+            position_quantization(Aabb::empty()),
+            (cgmath::vec3(1.0, 1.0, 1.0), cgmath::vec3(0.0, 0.0, 0.0))
+        );
+    }
+
+    #[test]
+    fn normalized_bytes() {
+        assert_eq!(
+            [snorm(-1.0), snorm(0.0), snorm(1.0), snorm(2.0)],
+            [-127, 0, 127, 127]
+        );
+        assert_eq!([unorm(-1.0), unorm(0.5), unorm(1.0)], [0, 128, 255]);
+    }
+}
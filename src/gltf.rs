@@ -5,24 +5,49 @@ use gltf::buffer::Data;
 use gltf::Accessor;
 use gltf::Semantic;
 
-use crate::material::Material;
+use cgmath::{InnerSpace, SquareMatrix};
+
+use crate::bounds::Aabb;
+use crate::fixed_camera::{FixedCameras, Shot, Trigger, TriggerVolume};
+use crate::geometry::VertexFormat;
+use crate::lod::{LodGroup, LodSwitch, DEFAULT_LOD_STEP};
+use crate::material::{AlphaMode, Material, Parameter, TextureRef};
 use crate::mesh::{Mesh, Primitive};
-use crate::texture::Texture2D;
+use crate::sky::SkyTexture;
+use crate::texture::{FilterPolicy, PixelFormat, Texture2D, TextureCube};
 
 use crate::render::AssetStorage;
 use crate::render::Node;
+use crate::render::SceneSettings;
+use crate::render::{Fog, FogMode};
+use crate::technique::{TechniqueRegistry, DEFAULT_TECHNIQUE};
+
+/// Everything `read_from_file` loads from a scene
+pub(crate) type Scene = (
+    AssetStorage,
+    Vec<Node>,
+    SceneSettings,
+    FixedCameras,
+    Vec<LodGroup>,
+    TextureStats,
+);
 
 // TODO: load camera position from file
-pub fn read_from_file<P: AsRef<Path>>(path: P) -> (AssetStorage, Vec<Node>) {
-    let (document, buffers, images) = gltf::import(path).unwrap();
-    assert_eq!(buffers.len(), document.buffers().count());
-    assert_eq!(images.len(), document.images().count());
+pub(crate) fn read_from_file<P: AsRef<Path>>(
+    path: P,
+    techniques: TechniqueRegistry,
+) -> Result<Scene, String> {
+    let path = path.as_ref();
+    let (document, buffers, images) =
+        gltf::import(path).map_err(|error| format!("{}: {error}", path.display()))?;
 
     let mut storage = AssetStorage {
         meshes: Vec::with_capacity(document.meshes().len()),
         materials: Vec::with_capacity(document.materials().len()),
         textures2d: Vec::with_capacity(images.len()),
         programs: Vec::new(),
+        techniques,
+        sky: None,
     };
 
     let mut material_indexes: Vec<Vec<usize>> = Vec::with_capacity(document.meshes().len());
@@ -31,33 +56,63 @@ pub fn read_from_file<P: AsRef<Path>>(path: P) -> (AssetStorage, Vec<Node>) {
         let mut material_index = Vec::with_capacity(gltf_mesh.primitives().len());
 
         for primitive in gltf_mesh.primitives() {
-            let positions = primitive.get(&Semantic::Positions).unwrap();
-            assert!(positions.data_type() == gltf::accessor::DataType::F32);
-
-            let normals = primitive.get(&Semantic::Normals).unwrap();
-            assert!(normals.data_type() == gltf::accessor::DataType::F32);
-
-            let tex_coords_0 = primitive.get(&Semantic::TexCoords(0)).unwrap();
-            assert!(tex_coords_0.data_type() == gltf::accessor::DataType::F32);
-
-            let indices = primitive.indices().unwrap();
-            assert!(
-                indices.data_type() == gltf::accessor::DataType::U16
-                    || indices.data_type() == gltf::accessor::DataType::U32
-            );
+            let error = |error: String| {
+                format!(
+                    "Mesh {} primitive {}: {error}",
+                    gltf_mesh.name().unwrap_or(&gltf_mesh.index().to_string()),
+                    primitive.index()
+                )
+            };
 
-            let positions = get_data::<cgmath::Vector3<f32>>(positions, &buffers);
-            let normals = get_data::<cgmath::Vector3<f32>>(normals, &buffers);
-            let tex_coords_0 = get_data::<cgmath::Vector2<f32>>(tex_coords_0, &buffers);
+            let positions = float_attribute(&primitive, Semantic::Positions).map_err(error)?;
+            let normals = float_attribute(&primitive, Semantic::Normals).map_err(error)?;
+            let tex_coords_0 =
+                float_attribute(&primitive, Semantic::TexCoords(0)).map_err(error)?;
+            let tex_coords_1 = match primitive.get(&Semantic::TexCoords(1)) {
+                Some(_) => {
+                    Some(float_attribute(&primitive, Semantic::TexCoords(1)).map_err(error)?)
+                }
+                None => None,
+            };
 
-            let indices: Vec<u32> = if indices.data_type() == gltf::accessor::DataType::U16 {
-                let indices = get_data::<u16>(indices, &buffers);
-                indices.into_iter().map(|x| x as u32).collect()
-            } else {
-                get_data::<u32>(indices, &buffers)
+            // Any COLOR_0 format, normalized integers are converted to floats
+            let colors = primitive
+                .reader(|buffer| Some(&buffers[buffer.index()]))
+                .read_colors(0)
+                .map(|colors| colors.into_rgba_f32().map(cgmath::Vector4::from).collect());
+
+            let indices = primitive
+                .indices()
+                .ok_or_else(|| error("not indexed".to_string()))?;
+
+            let positions = get_data::<cgmath::Vector3<f32>>(positions, &buffers).map_err(error)?;
+            let normals = get_data::<cgmath::Vector3<f32>>(normals, &buffers).map_err(error)?;
+            let tex_coords_0 =
+                get_data::<cgmath::Vector2<f32>>(tex_coords_0, &buffers).map_err(error)?;
+            let tex_coords_1 = tex_coords_1
+                .map(|x| get_data::<cgmath::Vector2<f32>>(x, &buffers))
+                .transpose()
+                .map_err(error)?;
+
+            let indices: Vec<u32> = match indices.data_type() {
+                gltf::accessor::DataType::U16 => {
+                    let indices = get_data::<u16>(indices, &buffers).map_err(error)?;
+                    indices.into_iter().map(|x| x as u32).collect()
+                }
+                gltf::accessor::DataType::U32 => {
+                    get_data::<u32>(indices, &buffers).map_err(error)?
+                }
+                data_type => return Err(error(format!("unsupported index type {data_type:?}"))),
             };
 
-            primitives.push(Primitive::new(positions, normals, tex_coords_0, indices));
+            primitives.push(Primitive::new(
+                positions,
+                normals,
+                tex_coords_0,
+                tex_coords_1,
+                colors,
+                indices,
+            ));
             material_index.push(primitive.material().index().unwrap_or_default());
         }
 
@@ -65,115 +120,761 @@ pub fn read_from_file<P: AsRef<Path>>(path: P) -> (AssetStorage, Vec<Node>) {
         material_indexes.push(material_index);
     }
 
+    let mut texture_cache = TextureCache::new(&images);
     for gltf_material in document.materials() {
         let mut material = Material::default();
         let pbr = gltf_material.pbr_metallic_roughness();
 
         if let Some(albedo) = pbr.base_color_texture() {
-            let texture = get_texture(albedo.texture(), &images);
-
-            material.albedo = Some(storage.textures2d.len());
-            storage.textures2d.push(texture);
+            material.albedo = Some(get_texture_ref(
+                albedo.texture(),
+                albedo.tex_coord(),
+                albedo.texture_transform().map(TextureTransform::from),
+                &mut texture_cache,
+                &mut storage,
+            ));
         }
 
         let color = pbr.base_color_factor();
-        material.base_color = Some(cgmath::vec3(color[0], color[1], color[2]));
+        material.base_color = cgmath::vec4(color[0], color[1], color[2], color[3]);
+
+        if let Some(emissive) = gltf_material.emissive_texture() {
+            material.emissive = Some(get_texture_ref(
+                emissive.texture(),
+                emissive.tex_coord(),
+                emissive.texture_transform().map(TextureTransform::from),
+                &mut texture_cache,
+                &mut storage,
+            ));
+        }
 
-        // TODO
+        let color = gltf_material.emissive_factor();
+        material.emissive_color = cgmath::vec3(color[0], color[1], color[2]);
+
+        if let Some(normal) = gltf_material.normal_texture() {
+            // The gltf crate only parses KHR_texture_transform of plain texture infos
+            let transform = normal
+                .extension_value("KHR_texture_transform")
+                .and_then(|value| {
+                    TextureTransform::from_json(value)
+                        .map_err(|error| {
+                            eprintln!(
+                                "Material {}: invalid normal texture KHR_texture_transform: {error}",
+                                gltf_material.index().unwrap_or_default()
+                            )
+                        })
+                        .ok()
+                });
+            material.normal = Some(texture_ref(
+                normal.texture().index(),
+                normal.tex_coord(),
+                transform,
+            ));
+            material.normal_scale = normal.scale();
+        }
+
+        if let Some(metallic_roughness) = pbr.metallic_roughness_texture() {
+            material.metallic_roughness = Some(texture_ref(
+                metallic_roughness.texture().index(),
+                metallic_roughness.tex_coord(),
+                metallic_roughness
+                    .texture_transform()
+                    .map(TextureTransform::from),
+            ));
+        }
+
+        material.metallic = pbr.metallic_factor();
+        material.roughness = pbr.roughness_factor();
+
+        material.alpha_mode = match gltf_material.alpha_mode() {
+            gltf::material::AlphaMode::Opaque => AlphaMode::Opaque,
+            gltf::material::AlphaMode::Mask => {
+                AlphaMode::Mask(gltf_material.alpha_cutoff().unwrap_or(0.5))
+            }
+            gltf::material::AlphaMode::Blend => AlphaMode::Blend,
+        };
+        material.double_sided = gltf_material.double_sided();
+        material.unlit = gltf_material.unlit();
+        material.filter_policy = filter_policy(gltf_material.extras());
+        material.affine = extras_bool(gltf_material.extras(), "psx_affine");
+        material.dithered = extras_bool(gltf_material.extras(), "psx_dither");
+        material.technique = technique(gltf_material.extras(), &storage.techniques);
+        material.parameters = parameters(gltf_material.extras());
 
         storage.materials.push(material);
     }
 
-    let default_scene = document.default_scene().unwrap();
-    let mut node_data = HashMap::with_capacity(document.nodes().len());
-    for gltf_node in default_scene.nodes() {
-        let transform = cgmath::Matrix4::from(gltf_node.transform().matrix());
-        if let Some(mesh) = gltf_node.mesh() {
-            let node = Node {
-                mesh: mesh.index(),
-                materials: material_indexes[mesh.index()].clone(),
-                transform,
-            };
-
-            node_data.insert(gltf_node.index(), node);
-        }
+    let default_scene = document
+        .default_scene()
+        .or_else(|| document.scenes().next())
+        .ok_or_else(|| format!("{}: no scene", path.display()))?;
+    let scene_extras = default_scene.extras();
+    let draw_distance = extras_f32(scene_extras, "psx_draw_distance");
+    let settings = SceneSettings {
+        filter_policy: filter_policy(scene_extras).unwrap_or_default(),
+        lighting: extras_bool(scene_extras, "psx_lighting").unwrap_or_default(),
+        affine: extras_bool(scene_extras, "psx_affine").unwrap_or_default(),
+        dither: extras_bool(scene_extras, "psx_dither").unwrap_or_default(),
+        vertex_format: vertex_format(scene_extras).unwrap_or_default(),
+        release_geometry: extras_bool(scene_extras, "psx_release_geometry").unwrap_or_default(),
+        draw_distance,
+        fog: fog(scene_extras, draw_distance),
+        clear_color: extras_vector3(scene_extras, "psx_clear_color"),
+        ambient_color: extras_vector3(scene_extras, "psx_ambient_color"),
+        sun_direction: extras_vector3(scene_extras, "psx_sun_direction"),
+        sun_color: extras_vector3(scene_extras, "psx_sun_color"),
+    };
+    let lod_step = extras_f32(scene_extras, "psx_lod_step").unwrap_or(DEFAULT_LOD_STEP);
+    storage.sky = sky(scene_extras, &document, &images);
 
-        for child in gltf_node.children() {
-            parse_nodes_recursive(child, transform, &mut node_data, &material_indexes);
-        }
+    let mut parsed = ParsedNodes {
+        nodes: HashMap::with_capacity(document.nodes().len()),
+        ..Default::default()
+    };
+    for gltf_node in default_scene.nodes() {
+        parse_nodes_recursive(
+            gltf_node,
+            None,
+            cgmath::Matrix4::identity(),
+            &mut parsed,
+            &material_indexes,
+            &storage.meshes,
+        );
     }
 
-    return (storage, node_data.into_values().collect());
+    let triggers = parsed
+        .triggers
+        .into_iter()
+        .filter_map(|(name, volume)| {
+            let shot = parsed.shots.iter().position(|shot| shot.name == name);
+            if shot.is_none() {
+                eprintln!("Trigger volume refers to unknown camera \"{name}\"");
+            }
+
+            Some(Trigger {
+                shot: shot?,
+                volume,
+            })
+        })
+        .collect();
+    let fixed_cameras = FixedCameras::new(parsed.shots, triggers);
+
+    let (gltf_indices, mut nodes): (Vec<usize>, Vec<Node>) = parsed.nodes.into_iter().unzip();
+    let lod_groups = lod_groups(parsed.lod_levels, &gltf_indices, &mut nodes, lod_step);
+
+    return Ok((
+        storage,
+        nodes,
+        settings,
+        fixed_cameras,
+        lod_groups,
+        texture_cache.stats,
+    ));
+
+    #[derive(Default)]
+    struct ParsedNodes {
+        nodes: HashMap<usize, Node>,
+        shots: Vec<Shot>,
+        /// Volumes with the name of the camera node they cut to
+        triggers: Vec<(String, TriggerVolume)>,
+        lod_levels: Vec<LodLevel>,
+    }
 
     fn parse_nodes_recursive(
         gltf_node: gltf::Node,
+        parent: Option<usize>,
         parent_transform: cgmath::Matrix4<f32>,
-        data: &mut HashMap<usize, Node>,
+        parsed: &mut ParsedNodes,
         material_indexes: &[Vec<usize>],
+        meshes: &[Mesh],
     ) {
         let transform: cgmath::Matrix4<f32> =
             parent_transform * cgmath::Matrix4::from(gltf_node.transform().matrix());
 
-        if let Some(mesh) = gltf_node.mesh() {
+        if let Some(shot) = extras_str(gltf_node.extras(), "psx_shot") {
+            // Trigger volumes are not rendered: a mesh is a convex volume, an empty is the
+            // axis aligned bounds of a unit cube (Blender's cube empty)
+            let volume = match gltf_node.mesh() {
+                Some(mesh) => TriggerVolume::convex(
+                    meshes[mesh.index()]
+                        .0
+                        .iter()
+                        .flat_map(|primitive| primitive.triangles())
+                        .map(|triangle| {
+                            triangle.map(|vertex| (transform * vertex.extend(1.0)).truncate())
+                        }),
+                ),
+                None => TriggerVolume::Aabb(
+                    Aabb::from_points(&[
+                        cgmath::vec3(-1.0, -1.0, -1.0),
+                        cgmath::vec3(1.0, 1.0, 1.0),
+                    ])
+                    .transform(transform),
+                ),
+            };
+            parsed.triggers.push((shot, volume));
+        } else if let Some(mesh) = gltf_node.mesh() {
             let node = Node {
                 mesh: mesh.index(),
                 materials: material_indexes[mesh.index()].clone(),
+                programs: Vec::new(),
+                bounds: Vec::new(),
+                instance_group: None,
+                lod: None,
                 transform,
             };
 
-            data.insert(gltf_node.index(), node);
+            if let Some(level) = LodLevel::parse(&gltf_node, parent) {
+                parsed.lod_levels.push(level);
+            }
+            parsed.nodes.insert(gltf_node.index(), node);
+        }
+
+        if let Some(camera) = gltf_node.camera() {
+            match camera.projection() {
+                gltf::camera::Projection::Perspective(perspective) => {
+                    // glTF cameras look down their local -Z
+                    let front = (transform * cgmath::vec4(0.0, 0.0, -1.0, 0.0)).truncate();
+                    parsed.shots.push(Shot {
+                        name: gltf_node.name().unwrap_or_default().to_string(),
+                        position: transform.w.truncate(),
+                        front: front.normalize(),
+                        fovy: cgmath::Rad(perspective.yfov()),
+                        tracking: extras_bool(gltf_node.extras(), "psx_track").unwrap_or_default(),
+                    });
+                }
+                gltf::camera::Projection::Orthographic(_) => eprintln!(
+                    "Orthographic camera \"{}\" can't be a fixed shot",
+                    gltf_node.name().unwrap_or_default()
+                ),
+            }
         }
 
         for child_node in gltf_node.children() {
-            parse_nodes_recursive(child_node, transform, data, material_indexes);
+            parse_nodes_recursive(
+                child_node,
+                Some(gltf_node.index()),
+                transform,
+                parsed,
+                material_indexes,
+                meshes,
+            );
+        }
+    }
+}
+
+/// Mesh node that is a level of detail, named `<group>_LOD<level>` or with
+/// `"psx_lod_group"` and `"psx_lod_level"` extras
+struct LodLevel {
+    /// Parent node and group name, levels of a group are siblings
+    group: (Option<usize>, String),
+    level: usize,
+    node: usize,
+    switch: Option<LodSwitch>,
+}
+
+impl LodLevel {
+    fn parse(gltf_node: &gltf::Node, parent: Option<usize>) -> Option<Self> {
+        let extras = gltf_node.extras();
+        let (name, level) = match extras_str(extras, "psx_lod_group") {
+            Some(name) => {
+                let level = extras_value(extras, "psx_lod_level")
+                    .and_then(|level| level.as_u64())
+                    .unwrap_or_default();
+                (name, level as usize)
+            }
+            None => {
+                let name = gltf_node.name()?;
+                // Blender appends .001 etc. to duplicated names
+                let name = match name.rsplit_once('.') {
+                    Some((base, suffix)) if suffix.bytes().all(|byte| byte.is_ascii_digit()) => {
+                        base
+                    }
+                    _ => name,
+                };
+                let (name, level) = name.rsplit_once("_LOD")?;
+                (name.to_string(), level.parse().ok()?)
+            }
+        };
+
+        let switch = match (
+            extras_f32(extras, "psx_lod_distance"),
+            extras_f32(extras, "psx_lod_screen_size"),
+        ) {
+            (Some(distance), _) => Some(LodSwitch::Distance(distance)),
+            (None, Some(size)) => Some(LodSwitch::ScreenSize(size)),
+            (None, None) => None,
+        };
+
+        Some(Self {
+            group: (parent, name),
+            level,
+            node: gltf_node.index(),
+            switch,
+        })
+    }
+}
+
+/// Groups levels by parent and name, groups of a single level are left as plain nodes.
+/// Levels without a switch change every `step` units of distance.
+fn lod_groups(
+    levels: Vec<LodLevel>,
+    gltf_indices: &[usize],
+    nodes: &mut [Node],
+    step: f32,
+) -> Vec<LodGroup> {
+    let node_indices: HashMap<usize, usize> = gltf_indices
+        .iter()
+        .enumerate()
+        .map(|(index, &gltf_index)| (gltf_index, index))
+        .collect();
+
+    let mut candidates: HashMap<(Option<usize>, String), Vec<LodLevel>> = HashMap::new();
+    for level in levels {
+        candidates
+            .entry(level.group.clone())
+            .or_default()
+            .push(level);
+    }
+
+    let mut candidates: Vec<_> = candidates
+        .into_values()
+        .filter(|levels| levels.len() > 1)
+        .collect();
+    for levels in candidates.iter_mut() {
+        levels.sort_by_key(|level| level.level);
+    }
+    // Deterministic group order
+    candidates.sort_by_key(|levels| levels[0].node);
+
+    let mut groups = Vec::with_capacity(candidates.len());
+    for levels in candidates {
+        let mut group_levels = Vec::with_capacity(levels.len());
+        let mut switches = Vec::with_capacity(levels.len() - 1);
+
+        for (i, level) in levels.iter().enumerate() {
+            let node = node_indices[&level.node];
+            nodes[node].lod = Some((groups.len(), i));
+            group_levels.push(node);
+
+            if i > 0 {
+                switches.push(level.switch.unwrap_or(LodSwitch::Distance(step * i as f32)));
+            }
         }
+
+        groups.push(LodGroup::new(group_levels, switches));
     }
+
+    groups
+}
+
+/// Accessor of a float vertex attribute
+fn float_attribute<'a>(
+    primitive: &gltf::Primitive<'a>,
+    semantic: Semantic,
+) -> Result<Accessor<'a>, String> {
+    let accessor = primitive
+        .get(&semantic)
+        .ok_or_else(|| format!("missing {semantic:?} attribute"))?;
+    if accessor.data_type() != gltf::accessor::DataType::F32 {
+        return Err(format!(
+            "{semantic:?} has to be stored as floats, not {:?}",
+            accessor.data_type()
+        ));
+    }
+
+    Ok(accessor)
 }
 
-fn get_data<T: bytemuck::Pod>(accessor: Accessor, buffers: &[Data]) -> Vec<T> {
-    let view = accessor.view().unwrap();
+fn get_data<T: bytemuck::Pod>(accessor: Accessor, buffers: &[Data]) -> Result<Vec<T>, String> {
+    let view = accessor
+        .view()
+        .ok_or_else(|| format!("accessor {} has no buffer view", accessor.index()))?;
 
     let buffer = &buffers[view.buffer().index()][view.offset()..view.offset() + view.length()];
     let buffer = &buffer[accessor.offset()..];
 
-    assert!(accessor.size() == view.stride().unwrap_or(accessor.size()));
+    if accessor.size() != view.stride().unwrap_or(accessor.size()) {
+        return Err(format!("accessor {} is interleaved", accessor.index()));
+    }
+
+    bytemuck::try_cast_slice(buffer)
+        .map(<[T]>::to_vec)
+        .map_err(|error| format!("accessor {}: {error}", accessor.index()))
+}
+
+fn get_texture_ref(
+    texture: gltf::Texture,
+    tex_coord: u32,
+    transform: Option<TextureTransform>,
+    cache: &mut TextureCache,
+    storage: &mut AssetStorage,
+) -> TextureRef {
+    let texture = cache.get(texture, &mut storage.textures2d);
+    texture_ref(texture, tex_coord, transform)
+}
+
+fn texture_ref(texture: usize, tex_coord: u32, transform: Option<TextureTransform>) -> TextureRef {
+    let mut texture_ref = TextureRef::new(texture, tex_coord);
+
+    if let Some(transform) = transform {
+        texture_ref =
+            texture_ref.with_transform(transform.offset, transform.rotation, transform.scale);
+        texture_ref.tex_coord = transform.tex_coord.unwrap_or(texture_ref.tex_coord);
+    }
+
+    texture_ref
+}
+
+/// KHR_texture_transform of a texture reference
+#[derive(Debug, Clone, Copy)]
+struct TextureTransform {
+    offset: [f32; 2],
+    rotation: f32,
+    scale: [f32; 2],
+    tex_coord: Option<u32>,
+}
+
+impl From<gltf::texture::TextureTransform<'_>> for TextureTransform {
+    fn from(transform: gltf::texture::TextureTransform) -> Self {
+        Self {
+            offset: transform.offset(),
+            rotation: transform.rotation(),
+            scale: transform.scale(),
+            tex_coord: transform.tex_coord(),
+        }
+    }
+}
 
-    bytemuck::cast_slice(buffer).to_vec()
+impl TextureTransform {
+    /// Reads the extension object, missing properties get their defaults
+    fn from_json(value: &serde_json::Value) -> Result<Self, String> {
+        let transform: gltf::json::extensions::texture::TextureTransform =
+            serde_json::from_value(value.clone()).map_err(|error| error.to_string())?;
+
+        Ok(Self {
+            offset: transform.offset.0,
+            rotation: transform.rotation.0,
+            scale: transform.scale.0,
+            tex_coord: transform.tex_coord,
+        })
+    }
 }
 
-fn get_texture<'a>(texture: gltf::Texture<'a>, images: &[gltf::image::Data]) -> Texture2D {
+/// Texture uploads of a loaded scene
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct TextureStats {
+    /// Textures uploaded to the GPU
+    pub uploaded: usize,
+    /// Uploaded texture maps of the materials, a shared texture counts once per use
+    pub references: usize,
+    pub uploaded_bytes: usize,
+    /// Bytes uploading a texture per reference would have taken in addition
+    pub saved_bytes: usize,
+}
+
+/// Uploads every (image, sampler) combination only once
+struct TextureCache<'a> {
+    images: &'a [gltf::image::Data],
+    textures: HashMap<(usize, Option<usize>), usize>,
+    stats: TextureStats,
+}
+
+impl<'a> TextureCache<'a> {
+    fn new(images: &'a [gltf::image::Data]) -> Self {
+        Self {
+            images,
+            textures: HashMap::new(),
+            stats: TextureStats::default(),
+        }
+    }
+
+    /// Returns index of the texture in `textures`, uploading it if needed
+    fn get(&mut self, texture: gltf::Texture, textures: &mut Vec<Texture2D>) -> usize {
+        let key = (texture.source().index(), texture.sampler().index());
+        let bytes = texture_size(&texture, self.images);
+
+        self.stats.references += 1;
+        if let Some(&index) = self.textures.get(&key) {
+            self.stats.saved_bytes += bytes;
+            return index;
+        }
+
+        self.stats.uploaded += 1;
+        self.stats.uploaded_bytes += bytes;
+
+        let index = textures.len();
+        textures.push(get_texture(texture, self.images));
+        self.textures.insert(key, index);
+
+        index
+    }
+}
+
+/// Approximate size of the texture on GPU, including the mipmap chain
+fn texture_size(texture: &gltf::Texture, images: &[gltf::image::Data]) -> usize {
+    let bytes = images[texture.source().index()].pixels.len();
+
+    match texture.sampler().min_filter() {
+        Some(gltf::texture::MinFilter::Nearest) | Some(gltf::texture::MinFilter::Linear) => bytes,
+        _ => bytes * 4 / 3,
+    }
+}
+
+fn get_texture(texture: gltf::Texture, images: &[gltf::image::Data]) -> Texture2D {
     let sampler = texture.sampler();
     let image = &images[texture.source().index()];
-    let (format, gl_type) = match image.format {
-        gltf::image::Format::R8 => (gl::R8, gl::UNSIGNED_BYTE),
-        gltf::image::Format::R8G8 => (gl::RG, gl::UNSIGNED_BYTE),
-        gltf::image::Format::R8G8B8 => (gl::RGB, gl::UNSIGNED_BYTE),
-        gltf::image::Format::R8G8B8A8 => (gl::RGBA, gl::UNSIGNED_BYTE),
-        gltf::image::Format::B8G8R8 => (gl::BGR, gl::UNSIGNED_BYTE),
-        gltf::image::Format::B8G8R8A8 => (gl::BGRA, gl::UNSIGNED_BYTE),
-        gltf::image::Format::R16 => (gl::R16, gl::UNSIGNED_SHORT),
-        gltf::image::Format::R16G16 => (gl::RG16, gl::UNSIGNED_SHORT),
-        gltf::image::Format::R16G16B16 => (gl::RGB16, gl::UNSIGNED_SHORT),
-        gltf::image::Format::R16G16B16A16 => (gl::RGBA16, gl::UNSIGNED_SHORT),
-    };
 
     Texture2D::new(
         sampler.wrap_s().as_gl_enum(),
         sampler.wrap_t().as_gl_enum(),
-        // Overwrite in favour of NEAR for better PSX emulation
-        // sampler
-        //     .mag_filter()
-        //     .unwrap_or(gltf::texture::MagFilter::Linear)
-        //     .as_gl_enum(),
-        // sampler
-        //     .min_filter()
-        //     .unwrap_or(gltf::texture::MinFilter::LinearMipmapLinear)
-        //     .as_gl_enum(),
-        gl::NEAREST,
-        gl::NEAREST,
+        // Authored filters, the active FilterPolicy decides whether they are used
+        sampler
+            .mag_filter()
+            .unwrap_or(gltf::texture::MagFilter::Linear)
+            .as_gl_enum(),
+        sampler
+            .min_filter()
+            .unwrap_or(gltf::texture::MinFilter::LinearMipmapLinear)
+            .as_gl_enum(),
         Some(&image.pixels),
-        format,
-        gl_type,
+        pixel_format(image),
         (image.width, image.height),
     )
 }
+
+fn pixel_format(image: &gltf::image::Data) -> PixelFormat {
+    match image.format {
+        gltf::image::Format::R8 => PixelFormat::new(gl::R8, gl::RED, gl::UNSIGNED_BYTE),
+        gltf::image::Format::R8G8 => PixelFormat::new(gl::RG8, gl::RG, gl::UNSIGNED_BYTE),
+        gltf::image::Format::R8G8B8 => PixelFormat::new(gl::RGB8, gl::RGB, gl::UNSIGNED_BYTE),
+        gltf::image::Format::R8G8B8A8 => PixelFormat::new(gl::RGBA8, gl::RGBA, gl::UNSIGNED_BYTE),
+        gltf::image::Format::R16 => PixelFormat::new(gl::R16, gl::RED, gl::UNSIGNED_SHORT),
+        gltf::image::Format::R16G16 => PixelFormat::new(gl::RG16, gl::RG, gl::UNSIGNED_SHORT),
+        gltf::image::Format::R16G16B16 => PixelFormat::new(gl::RGB16, gl::RGB, gl::UNSIGNED_SHORT),
+        gltf::image::Format::R16G16B16A16 => {
+            PixelFormat::new(gl::RGBA16, gl::RGBA, gl::UNSIGNED_SHORT)
+        }
+        gltf::image::Format::R32G32B32FLOAT => PixelFormat::new(gl::RGB32F, gl::RGB, gl::FLOAT),
+        gltf::image::Format::R32G32B32A32FLOAT => {
+            PixelFormat::new(gl::RGBA32F, gl::RGBA, gl::FLOAT)
+        }
+    }
+}
+
+/// Reads `"psx_sky": { "cubemap": [px, nx, py, ny, pz, nz] }`, `{ "equirect": image }` or
+/// `{ "backdrop": image, "repeat": 2.0, "horizon": 0.5 }`. Images are indices or names
+/// of glTF images, they don't need to be used by any material.
+fn sky(
+    extras: &gltf::json::Extras,
+    document: &gltf::Document,
+    images: &[gltf::image::Data],
+) -> Option<SkyTexture> {
+    let sky = extras_value(extras, "psx_sky")?;
+
+    let image = |value: &serde_json::Value| {
+        let index = match value {
+            serde_json::Value::String(name) => document
+                .images()
+                .position(|image| image.name() == Some(name.as_str())),
+            value => value
+                .as_u64()
+                .map(|index| index as usize)
+                .filter(|&index| index < images.len()),
+        };
+        if index.is_none() {
+            eprintln!("Sky image {value} doesn't exist");
+        }
+
+        index.map(|index| &images[index])
+    };
+    let flat = |image: &gltf::image::Data, wrap_t| {
+        Texture2D::new(
+            gl::REPEAT,
+            wrap_t,
+            gl::NEAREST,
+            gl::NEAREST,
+            Some(&image.pixels),
+            pixel_format(image),
+            (image.width, image.height),
+        )
+    };
+
+    if let Some(faces) = sky.get("cubemap") {
+        let faces: Option<Vec<_>> = faces.as_array()?.iter().map(image).collect();
+        let faces = faces?;
+        let Ok(faces) = <[&gltf::image::Data; 6]>::try_from(faces) else {
+            eprintln!("Sky cubemap needs 6 images: {sky}");
+            return None;
+        };
+
+        let size = faces[0].width;
+        if faces
+            .iter()
+            .any(|face| face.width != size || face.height != size || face.format != faces[0].format)
+        {
+            eprintln!("Sky cubemap faces have to be square images of the same size and format");
+            return None;
+        }
+
+        Some(SkyTexture::Cubemap(TextureCube::new(
+            faces.map(|face| face.pixels.as_slice()),
+            pixel_format(faces[0]),
+            size,
+        )))
+    } else if let Some(equirect) = sky.get("equirect") {
+        Some(SkyTexture::Equirect(flat(
+            image(equirect)?,
+            gl::CLAMP_TO_EDGE,
+        )))
+    } else if let Some(backdrop) = sky.get("backdrop") {
+        let image = image(backdrop)?;
+        let number = |key: &str| sky.get(key).and_then(serde_json::Value::as_f64);
+
+        Some(SkyTexture::Backdrop {
+            texture: flat(image, gl::CLAMP_TO_EDGE),
+            repeat: number("repeat").unwrap_or(1.0) as f32,
+            horizon: number("horizon").unwrap_or(0.5) as f32,
+            aspect: image.height as f32 / image.width as f32,
+        })
+    } else {
+        eprintln!("Sky needs a cubemap, equirect or backdrop image: {sky}");
+        None
+    }
+}
+
+fn extras_value(extras: &gltf::json::Extras, key: &str) -> Option<serde_json::Value> {
+    let extras: serde_json::Value = serde_json::from_str(extras.as_ref()?.get()).ok()?;
+    extras.get(key).cloned()
+}
+
+/// Reads a string property from glTF extras, e.g. `{ "psx_filter": "nearest" }`
+fn extras_str(extras: &gltf::json::Extras, key: &str) -> Option<String> {
+    extras_value(extras, key)?.as_str().map(str::to_string)
+}
+
+fn extras_bool(extras: &gltf::json::Extras, key: &str) -> Option<bool> {
+    extras_value(extras, key)?.as_bool()
+}
+
+fn extras_f32(extras: &gltf::json::Extras, key: &str) -> Option<f32> {
+    extras_value(extras, key)?
+        .as_f64()
+        .map(|value| value as f32)
+}
+
+/// Reads an `[r, g, b]` color or an `[x, y, z]` vector
+fn extras_vector3(extras: &gltf::json::Extras, key: &str) -> Option<cgmath::Vector3<f32>> {
+    vector3(&extras_value(extras, key)?, key)
+}
+
+fn vector3(value: &serde_json::Value, name: &str) -> Option<cgmath::Vector3<f32>> {
+    let components: Option<Vec<f32>> = value
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(|value| value.as_f64().map(|value| value as f32))
+        .collect();
+
+    match components.as_deref() {
+        Some(&[r, g, b]) => Some(cgmath::vec3(r, g, b)),
+        _ => {
+            eprintln!("\"{name}\" has to be an array of 3 numbers: {value}");
+            None
+        }
+    }
+}
+
+/// Reads `"psx_fog": { "color": [0.5, 0.5, 0.6], "mode": "linear", "start": 10.0, "end": 40.0 }`,
+/// the end defaults to the draw distance
+fn fog(extras: &gltf::json::Extras, draw_distance: Option<f32>) -> Option<Fog> {
+    let fog = extras_value(extras, "psx_fog")?;
+    let number = |key: &str| {
+        fog.get(key)
+            .and_then(serde_json::Value::as_f64)
+            .map(|value| value as f32)
+    };
+
+    let color = match fog.get("color") {
+        Some(value) => vector3(value, "color")?,
+        None => cgmath::vec3(0.0, 0.0, 0.0),
+    };
+
+    let mode = match fog.get("mode").and_then(serde_json::Value::as_str) {
+        Some(name) => FogMode::from_name(name).unwrap_or_else(|| {
+            eprintln!("Unknown fog mode \"{name}\"");
+            FogMode::default()
+        }),
+        None => FogMode::default(),
+    };
+
+    let Some(end) = number("end").or(draw_distance) else {
+        eprintln!("Fog needs an end distance or a draw distance: {fog}");
+        return None;
+    };
+
+    Some(Fog {
+        color,
+        mode,
+        start: number("start").unwrap_or_default(),
+        end,
+        density: number("density"),
+    })
+}
+
+fn vertex_format(extras: &gltf::json::Extras) -> Option<VertexFormat> {
+    let name = extras_str(extras, "psx_vertex_format")?;
+    let format = VertexFormat::from_name(&name);
+    if format.is_none() {
+        eprintln!("Unknown vertex format \"{name}\"");
+    }
+
+    format
+}
+
+fn filter_policy(extras: &gltf::json::Extras) -> Option<FilterPolicy> {
+    let name = extras_str(extras, "psx_filter")?;
+    let policy = FilterPolicy::from_name(&name);
+    if policy.is_none() {
+        eprintln!("Unknown texture filter policy \"{name}\"");
+    }
+
+    policy
+}
+
+/// Resolves `"psx_technique": "name"`, unknown names fall back to the default technique
+fn technique(extras: &gltf::json::Extras, techniques: &TechniqueRegistry) -> usize {
+    let name = extras_str(extras, "psx_technique").unwrap_or(DEFAULT_TECHNIQUE.to_string());
+    techniques.find(&name).unwrap_or_else(|| {
+        eprintln!("Unknown technique \"{name}\", using \"{DEFAULT_TECHNIQUE}\"");
+        techniques.find(DEFAULT_TECHNIQUE).unwrap()
+    })
+}
+
+/// Reads `"psx_parameters": { "uniform": 1.0, "other": [1.0, 0.5, 0.0] }`
+fn parameters(extras: &gltf::json::Extras) -> Vec<(String, Parameter)> {
+    let Some(serde_json::Value::Object(parameters)) = extras_value(extras, "psx_parameters") else {
+        return Vec::new();
+    };
+
+    parameters
+        .into_iter()
+        .filter_map(|(name, value)| {
+            let components: Option<Vec<f32>> = match &value {
+                serde_json::Value::Array(values) => values
+                    .iter()
+                    .map(|value| value.as_f64().map(|value| value as f32))
+                    .collect(),
+                value => value.as_f64().map(|value| vec![value as f32]),
+            };
+
+            let parameter =
+                components.and_then(|components| Parameter::from_components(&components));
+            if parameter.is_none() {
+                eprintln!("Unsupported material parameter \"{name}\": {value}");
+            }
+
+            parameter.map(|parameter| (name, parameter))
+        })
+        .collect()
+}
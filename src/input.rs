@@ -0,0 +1,332 @@
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+/// What the user wants to do, independent of the device it came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    MoveForward,
+    MoveBack,
+    MoveLeft,
+    MoveRight,
+    MoveUp,
+    MoveDown,
+    Sprint,
+    Jump,
+    ToggleCameraMode,
+    ToggleProjection,
+    CycleFilter,
+    CycleFrameRate,
+    FovDown,
+    FovUp,
+    FrameScene,
+    FrameSelected,
+    ToggleRecording,
+    TogglePlayback,
+    Screenshot,
+    PrintStats,
+    Quit,
+}
+
+impl Action {
+    const NAMES: [(Action, &'static str); 21] = [
+        (Action::MoveForward, "move_forward"),
+        (Action::MoveBack, "move_back"),
+        (Action::MoveLeft, "move_left"),
+        (Action::MoveRight, "move_right"),
+        (Action::MoveUp, "move_up"),
+        (Action::MoveDown, "move_down"),
+        (Action::Sprint, "sprint"),
+        (Action::Jump, "jump"),
+        (Action::ToggleCameraMode, "toggle_camera_mode"),
+        (Action::ToggleProjection, "toggle_projection"),
+        (Action::CycleFilter, "cycle_filter"),
+        (Action::CycleFrameRate, "cycle_frame_rate"),
+        (Action::FovDown, "fov_down"),
+        (Action::FovUp, "fov_up"),
+        (Action::FrameScene, "frame_scene"),
+        (Action::FrameSelected, "frame_selected"),
+        (Action::ToggleRecording, "toggle_recording"),
+        (Action::TogglePlayback, "toggle_playback"),
+        (Action::Screenshot, "screenshot"),
+        (Action::PrintStats, "print_stats"),
+        (Action::Quit, "quit"),
+    ];
+
+    /// Name used in the bindings file, e.g. "move_forward"
+    pub fn name(self) -> &'static str {
+        Self::NAMES
+            .iter()
+            .find(|(action, _)| *action == self)
+            .map(|(_, name)| *name)
+            .unwrap()
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::NAMES
+            .iter()
+            .find(|(_, action_name)| *action_name == name)
+            .map(|(action, _)| *action)
+    }
+}
+
+/// Actions of this frame and analog input, filled by the platform layer
+#[derive(Debug, Default, Clone)]
+pub struct InputState {
+    held: HashSet<Action>,
+    /// Pressed since the last `begin_frame`
+    pressed: Vec<Action>,
+
+    /// Movement stick after the dead zone, x to the right and y forward, -1 to 1
+    pub move_axis: (f32, f32),
+    /// Look stick after the dead zone, x to the right and y down, -1 to 1
+    pub look_axis: (f32, f32),
+    pub mouse_rel: (i32, i32),
+    /// Mouse wheel steps, positive away from the user
+    pub wheel: i32,
+}
+
+impl InputState {
+    /// Forgets per-frame input, held actions and stick positions stay
+    pub fn begin_frame(&mut self) {
+        self.pressed.clear();
+        self.mouse_rel = (0, 0);
+        self.wheel = 0;
+    }
+
+    pub fn press(&mut self, action: Action) {
+        if self.held.insert(action) {
+            self.pressed.push(action);
+        }
+    }
+
+    pub fn release(&mut self, action: Action) {
+        self.held.remove(&action);
+    }
+
+    pub fn is_held(&self, action: Action) -> bool {
+        self.held.contains(&action)
+    }
+
+    /// Actions pressed this frame in order
+    pub fn pressed(&self) -> &[Action] {
+        &self.pressed
+    }
+
+    /// Digital and analog input combined, 0 to 1
+    pub fn amount(&self, action: Action) -> f32 {
+        let digital = if self.is_held(action) { 1.0 } else { 0.0 };
+        let analog = match action {
+            Action::MoveForward => self.move_axis.1,
+            Action::MoveBack => -self.move_axis.1,
+            Action::MoveRight => self.move_axis.0,
+            Action::MoveLeft => -self.move_axis.0,
+            _ => 0.0,
+        };
+
+        f32::max(digital, analog.max(0.0))
+    }
+}
+
+/// Maps device input names to actions, loaded from a JSON file:
+///
+/// ```json
+/// {
+///     "keys": { "W": "move_forward", "Left Shift": "sprint" },
+///     "buttons": { "a": "jump" },
+///     "dead_zone": 0.2,
+///     "look_speed": 3.0
+/// }
+/// ```
+///
+/// Key and button names are the platform's (SDL scancode and game controller
+/// button names). Listed inputs replace their default action, `null` unbinds them,
+/// the other default inputs stay bound.
+#[derive(Debug, Clone)]
+pub struct Bindings {
+    keys: HashMap<String, Action>,
+    buttons: HashMap<String, Action>,
+    /// Stick deflection ignored around the center, 0 to 1
+    pub dead_zone: f32,
+    /// Camera turn rate at full look stick deflection, radians per second
+    pub look_speed: f32,
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        let keys = [
+            ("W", Action::MoveForward),
+            ("S", Action::MoveBack),
+            ("A", Action::MoveLeft),
+            ("D", Action::MoveRight),
+            ("E", Action::MoveUp),
+            ("Q", Action::MoveDown),
+            ("Left Shift", Action::Sprint),
+            ("Space", Action::Jump),
+            ("C", Action::ToggleCameraMode),
+            ("O", Action::ToggleProjection),
+            ("F", Action::CycleFilter),
+            ("T", Action::CycleFrameRate),
+            ("[", Action::FovDown),
+            ("]", Action::FovUp),
+            ("Home", Action::FrameScene),
+            (".", Action::FrameSelected),
+            ("R", Action::ToggleRecording),
+            ("P", Action::TogglePlayback),
+            ("F12", Action::Screenshot),
+            ("F3", Action::PrintStats),
+            ("Escape", Action::Quit),
+        ];
+        let buttons = [
+            ("a", Action::Jump),
+            ("leftstick", Action::Sprint),
+            ("rightshoulder", Action::MoveUp),
+            ("leftshoulder", Action::MoveDown),
+            ("y", Action::ToggleCameraMode),
+            ("x", Action::FrameSelected),
+            ("back", Action::FrameScene),
+            ("start", Action::Screenshot),
+        ];
+
+        Self {
+            keys: keys
+                .into_iter()
+                .map(|(key, action)| (key.to_string(), action))
+                .collect(),
+            buttons: buttons
+                .into_iter()
+                .map(|(button, action)| (button.to_string(), action))
+                .collect(),
+            dead_zone: 0.2,
+            look_speed: 3.0,
+        }
+    }
+}
+
+impl Bindings {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+        let error = |error: &dyn std::fmt::Display| format!("{}: {error}", path.display());
+
+        let text = std::fs::read_to_string(path).map_err(|e| error(&e))?;
+        let config: serde_json::Value = serde_json::from_str(&text).map_err(|e| error(&e))?;
+
+        let mut bindings = Self::default();
+        if let Some(keys) = config.get("keys") {
+            merge(&mut bindings.keys, parse_map(keys).map_err(|e| error(&e))?);
+        }
+        if let Some(buttons) = config.get("buttons") {
+            merge(
+                &mut bindings.buttons,
+                parse_map(buttons).map_err(|e| error(&e))?,
+            );
+        }
+        if let Some(dead_zone) = config.get("dead_zone").and_then(serde_json::Value::as_f64) {
+            bindings.dead_zone = (dead_zone as f32).clamp(0.0, 0.95);
+        }
+        if let Some(look_speed) = config.get("look_speed").and_then(serde_json::Value::as_f64) {
+            bindings.look_speed = look_speed as f32;
+        }
+
+        Ok(bindings)
+    }
+
+    pub fn key(&self, name: &str) -> Option<Action> {
+        self.keys.get(name).copied()
+    }
+
+    pub fn button(&self, name: &str) -> Option<Action> {
+        self.buttons.get(name).copied()
+    }
+
+    /// Actions no key or button triggers
+    pub fn unbound_actions(&self) -> Vec<Action> {
+        Action::NAMES
+            .iter()
+            .map(|(action, _)| *action)
+            .filter(|action| {
+                !self.keys.values().any(|bound| bound == action)
+                    && !self.buttons.values().any(|bound| bound == action)
+            })
+            .collect()
+    }
+
+    /// Radial dead zone, the rest of the range is rescaled to start at 0
+    pub fn stick(&self, (x, y): (f32, f32)) -> (f32, f32) {
+        let length = (x * x + y * y).sqrt();
+        if length <= self.dead_zone {
+            return (0.0, 0.0);
+        }
+
+        let scale = ((length - self.dead_zone) / (1.0 - self.dead_zone)).min(1.0) / length;
+        (x * scale, y * scale)
+    }
+}
+
+/// `{ "input name": "action_name" }`, `null` unbinds the input
+fn parse_map(value: &serde_json::Value) -> Result<HashMap<String, Option<Action>>, String> {
+    let map = value
+        .as_object()
+        .ok_or_else(|| "expected an object of \"input\": \"action\"".to_string())?;
+
+    map.iter()
+        .map(|(input, action)| {
+            if action.is_null() {
+                return Ok((input.clone(), None));
+            }
+
+            let name = action.as_str().unwrap_or_default();
+            let action = Action::from_name(name)
+                .ok_or_else(|| format!("unknown action {action} for \"{input}\""))?;
+
+            Ok((input.clone(), Some(action)))
+        })
+        .collect()
+}
+
+/// Configured inputs replace or unbind the same default inputs, the others are kept
+fn merge(defaults: &mut HashMap<String, Action>, user: HashMap<String, Option<Action>>) {
+    for (input, action) in user {
+        match action {
+            Some(action) => defaults.insert(input, action),
+            None => defaults.remove(&input),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rebinding_a_key_keeps_the_other_defaults() {
+        let mut keys = Bindings::default().keys;
+        let user = HashMap::from([("J".to_string(), Some(Action::Jump))]);
+        merge(&mut keys, user);
+
+        assert_eq!(keys.get("J"), Some(&Action::Jump));
+        assert_eq!(keys.get("Space"), Some(&Action::Jump));
+        assert_eq!(keys.get("W"), Some(&Action::MoveForward));
+    }
+
+    #[test]
+    fn rebinding_a_default_key_replaces_its_action() {
+        let mut bindings = Bindings::default();
+        let user = HashMap::from([("W".to_string(), Some(Action::Jump))]);
+        merge(&mut bindings.keys, user);
+
+        assert_eq!(bindings.key("W"), Some(Action::Jump));
+        assert_eq!(bindings.key("S"), Some(Action::MoveBack));
+        assert_eq!(bindings.unbound_actions(), [Action::MoveForward]);
+    }
+
+    #[test]
+    fn null_unbinds_a_default_key() {
+        let mut bindings = Bindings::default();
+        let user = parse_map(&serde_json::json!({ "Space": null, "J": "jump" })).unwrap();
+        merge(&mut bindings.keys, user);
+
+        assert_eq!(bindings.key("Space"), None);
+        assert_eq!(bindings.key("J"), Some(Action::Jump));
+        assert!(bindings.unbound_actions().is_empty());
+    }
+}
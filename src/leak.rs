@@ -0,0 +1,99 @@
+//! Counting of live GL objects in debug builds, release builds compile it out
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum GlKind {
+    Buffer,
+    VertexArray,
+    Texture,
+    Framebuffer,
+    Shader,
+    Program,
+}
+
+#[cfg(debug_assertions)]
+impl GlKind {
+    const ALL: [GlKind; 6] = [
+        GlKind::Buffer,
+        GlKind::VertexArray,
+        GlKind::Texture,
+        GlKind::Framebuffer,
+        GlKind::Shader,
+        GlKind::Program,
+    ];
+
+    /// Slot of the kind's counter, every kind has its own below `ALL.len()`
+    fn index(self) -> usize {
+        match self {
+            GlKind::Buffer => 0,
+            GlKind::VertexArray => 1,
+            GlKind::Texture => 2,
+            GlKind::Framebuffer => 3,
+            GlKind::Shader => 4,
+            GlKind::Program => 5,
+        }
+    }
+}
+
+#[cfg(debug_assertions)]
+mod counters {
+    use super::GlKind;
+    use std::sync::atomic::{AtomicIsize, Ordering};
+
+    #[allow(clippy::declare_interior_mutable_const)]
+    const ZERO: AtomicIsize = AtomicIsize::new(0);
+    static LIVE: [AtomicIsize; GlKind::ALL.len()] = [ZERO; GlKind::ALL.len()];
+
+    pub(super) fn add(kind: GlKind, value: isize) {
+        LIVE[kind.index()].fetch_add(value, Ordering::Relaxed);
+    }
+
+    pub(super) fn live(kind: GlKind) -> isize {
+        LIVE[kind.index()].load(Ordering::Relaxed)
+    }
+}
+
+#[inline]
+pub(crate) fn created(_kind: GlKind) {
+    #[cfg(debug_assertions)]
+    counters::add(_kind, 1);
+}
+
+#[inline]
+pub(crate) fn deleted(_kind: GlKind) {
+    #[cfg(debug_assertions)]
+    counters::add(_kind, -1);
+}
+
+/// Prints GL objects that are still alive and returns their count.
+/// Should be called at shutdown after everything owning GL objects is dropped,
+/// always returns 0 in release builds.
+pub fn report() -> usize {
+    #[cfg(debug_assertions)]
+    {
+        let mut outstanding = 0;
+        for kind in GlKind::ALL {
+            let live = counters::live(kind);
+            if live != 0 {
+                eprintln!("Leaked GL objects: {live} of {kind:?}");
+                outstanding += live.unsigned_abs();
+            }
+        }
+
+        outstanding
+    }
+
+    #[cfg(not(debug_assertions))]
+    0
+}
+
+#[cfg(all(test, debug_assertions))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_kind_has_its_own_counter() {
+        for (i, kind) in GlKind::ALL.into_iter().enumerate() {
+            assert_eq!(kind.index(), i, "{kind:?}");
+        }
+    }
+}
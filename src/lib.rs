@@ -1,29 +1,29 @@
+pub mod app;
+pub mod bounds;
 mod buffer;
 pub mod camera;
-mod framebuffer;
+pub mod camera_path;
+pub mod collision;
+pub mod fixed_camera;
+pub mod framebuffer;
+pub mod geometry;
 pub mod gltf;
+pub mod input;
+pub mod leak;
+pub mod lod;
 mod material;
 mod mesh;
+pub mod overlay;
+pub mod platform;
 pub mod render;
-mod shader;
+mod render_queue;
+pub mod shader;
+mod sky;
+pub mod technique;
 pub mod texture;
 mod vao;
+pub mod walk;
 
 pub(crate) trait GlObject {
     fn glid(&self) -> gl::types::GLuint;
 }
-
-#[derive(Debug, Default, Clone)]
-pub struct InputState {
-    pub w: bool,
-    pub a: bool,
-    pub s: bool,
-    pub d: bool,
-    pub e: bool,
-    pub q: bool,
-
-    pub shift: bool,
-
-    pub mouse_pos: (i32, i32),
-    pub mouse_rel: (i32, i32),
-}
@@ -0,0 +1,134 @@
+use cgmath::prelude::*;
+use cgmath::Vector3;
+
+use crate::bounds::Aabb;
+
+/// Distance between levels of a group that don't set their own switch
+pub const DEFAULT_LOD_STEP: f32 = 10.0;
+
+/// When a level of detail replaces the more detailed level before it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LodSwitch {
+    /// Camera farther than this from the center of the group
+    Distance(f32),
+    /// Bounding sphere of the group covering less than this fraction of the viewport height
+    ScreenSize(f32),
+}
+
+/// Alternative nodes of one object, only the selected level is drawn
+#[derive(Debug, Clone)]
+pub struct LodGroup {
+    /// Node of every level, most detailed first
+    levels: Vec<usize>,
+    /// Switch of every level after the first
+    switches: Vec<LodSwitch>,
+    /// World space bounds of the most detailed level
+    pub(crate) bounds: Aabb,
+    selected: usize,
+}
+
+impl LodGroup {
+    pub fn new(levels: Vec<usize>, switches: Vec<LodSwitch>) -> Self {
+        assert_eq!(levels.len(), switches.len() + 1);
+
+        Self {
+            levels,
+            switches,
+            bounds: Aabb::empty(),
+            selected: 0,
+        }
+    }
+
+    pub fn levels(&self) -> &[usize] {
+        &self.levels
+    }
+
+    /// Level picked by the last `select`
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    /// Picks the least detailed level whose switch is reached when seen from `eye`
+    pub fn select(&mut self, eye: Vector3<f32>, projection: cgmath::Matrix4<f32>) -> usize {
+        let distance = (self.bounds.center() - eye).magnitude();
+        // Perspective projections divide by depth, orthographic ones don't
+        let screen_size = if projection.w.w == 0.0 {
+            self.bounds.radius() * projection.y.y / distance.max(f32::EPSILON)
+        } else {
+            self.bounds.radius() * projection.y.y
+        };
+
+        self.selected = self
+            .switches
+            .iter()
+            .take_while(|switch| match **switch {
+                LodSwitch::Distance(switch_distance) => distance > switch_distance,
+                LodSwitch::ScreenSize(switch_size) => screen_size < switch_size,
+            })
+            .count();
+
+        self.selected
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Group around the origin with a bounding sphere radius of 1
+    fn group(switches: Vec<LodSwitch>) -> LodGroup {
+        let mut group = LodGroup::new((0..=switches.len()).collect(), switches);
+        let half = 1.0 / 3f32.sqrt();
+        group.bounds = Aabb::from_points(&[
+            cgmath::vec3(-half, -half, -half),
+            cgmath::vec3(half, half, half),
+        ]);
+
+        group
+    }
+
+    fn perspective() -> cgmath::Matrix4<f32> {
+        cgmath::perspective(cgmath::Deg(90.0), 1.0, 0.1, 100.0)
+    }
+
+    #[test]
+    fn switches_by_distance() {
+        let mut group = group(vec![LodSwitch::Distance(10.0), LodSwitch::Distance(20.0)]);
+
+        assert_eq!(group.select(cgmath::vec3(0.0, 0.0, 5.0), perspective()), 0);
+        assert_eq!(group.select(cgmath::vec3(0.0, 0.0, 15.0), perspective()), 1);
+        assert_eq!(group.select(cgmath::vec3(0.0, 25.0, 0.0), perspective()), 2);
+        assert_eq!(group.selected(), 2);
+        // Exactly at the switch distance the detailed level stays
+        assert_eq!(group.select(cgmath::vec3(10.0, 0.0, 0.0), perspective()), 0);
+    }
+
+    #[test]
+    fn switches_by_screen_size() {
+        // With a 90° field of view the sphere covers 1 / distance of the viewport height
+        let mut group = group(vec![LodSwitch::ScreenSize(0.5), LodSwitch::ScreenSize(0.1)]);
+
+        assert_eq!(group.select(cgmath::vec3(0.0, 0.0, 1.5), perspective()), 0);
+        assert_eq!(group.select(cgmath::vec3(0.0, 0.0, 5.0), perspective()), 1);
+        assert_eq!(group.select(cgmath::vec3(0.0, 0.0, 50.0), perspective()), 2);
+    }
+
+    #[test]
+    fn orthographic_size_ignores_distance() {
+        let mut group = group(vec![LodSwitch::ScreenSize(0.5)]);
+        let orthographic = cgmath::ortho(-4.0, 4.0, -4.0, 4.0, 0.1, 100.0);
+
+        // Radius 1 of a view 8 units high
+        assert_eq!(group.select(cgmath::vec3(0.0, 0.0, 1.0), orthographic), 1);
+        assert_eq!(group.select(cgmath::vec3(0.0, 0.0, 90.0), orthographic), 1);
+    }
+
+    #[test]
+    fn levels_switch_in_order() {
+        // A later switch can't skip an earlier one that isn't reached
+        let mut group = group(vec![LodSwitch::Distance(30.0), LodSwitch::Distance(10.0)]);
+
+        assert_eq!(group.select(cgmath::vec3(0.0, 0.0, 20.0), perspective()), 0);
+        assert_eq!(group.select(cgmath::vec3(0.0, 0.0, 40.0), perspective()), 2);
+    }
+}
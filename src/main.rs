@@ -1,156 +1,102 @@
-use sdl2::event::{Event, WindowEvent};
-use sdl2::keyboard::{Mod, Scancode};
-use sdl2::video::GLProfile;
+use renderer::app::{self, App, Status};
+use renderer::input::Bindings;
+use renderer::platform::{self, sdl::SdlPlatform};
 
 const SCENE_PATH: &str = "scenes/Phasmophobia/Phasmophobia.gltf";
-
-const GL_MAJOR_VERSION: u8 = 3;
-const GL_MINOR_VERSION: u8 = 3;
-
-// TODO: Configure VSYNC instead
-const FRAMERATE: u32 = 60;
+const BINDINGS_PATH: &str = "input.json";
 
 fn main() {
-    let sdl_context = sdl2::init().unwrap();
-    let video_subsystem = sdl_context.video().unwrap();
-
-    let gl_attr = video_subsystem.gl_attr();
-    gl_attr.set_context_profile(GLProfile::Core);
-    gl_attr.set_context_version(GL_MAJOR_VERSION, GL_MINOR_VERSION);
-
-    let mut window = video_subsystem
-        .window("Window", 800, 600)
-        .opengl()
-        .resizable()
-        .build()
-        .unwrap();
+    let mut platform = SdlPlatform::new("Window", 800, 600).unwrap();
+    platform::load_gl(&platform);
 
-    window.set_grab(true);
-    sdl_context.mouse().set_relative_mouse_mode(true);
+    let bindings = Bindings::load(BINDINGS_PATH).unwrap_or_else(|error| {
+        eprintln!("Using default input bindings: {error}");
+        Bindings::default()
+    });
+    for action in bindings.unbound_actions() {
+        eprintln!("No key or button is bound to {}", action.name());
+    }
 
-    let _ctx = window.gl_create_context().unwrap();
-    gl::load_with(|name| video_subsystem.gl_get_proc_address(name) as *const _);
+    let mut app = match App::new(SCENE_PATH, bindings) {
+        Ok(app) => app,
+        Err(error) => {
+            eprintln!("Can't load {SCENE_PATH}: {error}");
+            std::process::exit(1);
+        }
+    };
 
-    debug_assert_eq!(gl_attr.context_profile(), GLProfile::Core);
-    debug_assert_eq!(
-        gl_attr.context_version(),
-        (GL_MAJOR_VERSION, GL_MINOR_VERSION)
+    let stats = app.world().load_stats();
+    println!("Built {} shader permutations", stats.shader_permutations);
+    println!(
+        "Instanced {} nodes in {} groups",
+        stats.instanced_nodes, stats.instance_groups
+    );
+    println!(
+        "Geometry: {} vertices, {} KiB ({:?})",
+        stats.vertices,
+        stats.geometry_bytes / 1024,
+        stats.vertex_format
     );
+    println!("Collision mesh: {} triangles", stats.collision_triangles);
+    println!(
+        "Textures: {} uploaded for {} references, {} KiB in use, {} KiB saved by sharing",
+        stats.textures.uploaded,
+        stats.textures.references,
+        stats.textures.uploaded_bytes / 1024,
+        stats.textures.saved_bytes / 1024,
+    );
+    app::run(&mut platform, &mut app, |status| match status {
+        Status::Screenshot(size, pixels) => save_screenshot(size, &pixels),
+        status if status.is_error() => eprintln!("{status}"),
+        status => println!("{status}"),
+    });
 
-    let mut render = renderer::render::World::from_gltf_file(SCENE_PATH);
-    let mut input_state = renderer::InputState::default();
-    let mut event_pump = sdl_context.event_pump().unwrap();
-    let mut timer = std::time::Instant::now();
+    // GL objects have to be released while the context is still alive
+    drop(app);
+    renderer::leak::report();
+}
 
-    'running: loop {
-        input_state.mouse_rel = (0, 0);
+/// Saves the low resolution frame as `screenshot-<unix time>.bmp`
+fn save_screenshot((width, height): (u32, u32), pixels: &[u8]) {
+    let time = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let path = format!("screenshot-{time}.bmp");
 
-        for event in event_pump.poll_iter() {
-            match event {
-                Event::Quit { .. }
-                | Event::KeyDown {
-                    scancode: Some(Scancode::Escape),
-                    ..
-                } => break 'running,
-                Event::KeyDown {
-                    scancode: Some(Scancode::W),
-                    keymod,
-                    ..
-                } => {
-                    input_state.w = true;
-                    input_state.shift = keymod == Mod::LSHIFTMOD;
-                }
-                Event::KeyDown {
-                    scancode: Some(Scancode::A),
-                    keymod,
-                    ..
-                } => {
-                    input_state.a = true;
-                    input_state.shift = keymod == Mod::LSHIFTMOD;
-                }
-                Event::KeyDown {
-                    scancode: Some(Scancode::S),
-                    keymod,
-                    ..
-                } => {
-                    input_state.s = true;
-                    input_state.shift = keymod == Mod::LSHIFTMOD;
-                }
-                Event::KeyDown {
-                    scancode: Some(Scancode::D),
-                    keymod,
-                    ..
-                } => {
-                    input_state.d = true;
-                    input_state.shift = keymod == Mod::LSHIFTMOD;
-                }
-                Event::KeyDown {
-                    scancode: Some(Scancode::E),
-                    keymod,
-                    ..
-                } => {
-                    input_state.e = true;
-                    input_state.shift = keymod == Mod::LSHIFTMOD;
-                }
-                Event::KeyDown {
-                    scancode: Some(Scancode::Q),
-                    keymod,
-                    ..
-                } => {
-                    input_state.q = true;
-                    input_state.shift = keymod == Mod::LSHIFTMOD;
-                }
-                Event::KeyUp {
-                    scancode: Some(Scancode::W),
-                    ..
-                } => input_state.w = false,
-                Event::KeyUp {
-                    scancode: Some(Scancode::A),
-                    ..
-                } => input_state.a = false,
-                Event::KeyUp {
-                    scancode: Some(Scancode::S),
-                    ..
-                } => input_state.s = false,
-                Event::KeyUp {
-                    scancode: Some(Scancode::D),
-                    ..
-                } => input_state.d = false,
-                Event::KeyUp {
-                    scancode: Some(Scancode::E),
-                    ..
-                } => input_state.e = false,
-                Event::KeyUp {
-                    scancode: Some(Scancode::Q),
-                    ..
-                } => input_state.q = false,
-                Event::MouseMotion {
-                    x, y, xrel, yrel, ..
-                } => {
-                    input_state.mouse_pos = (x, y);
-                    input_state.mouse_rel = (xrel, yrel);
-                }
-                // ---------------------------//
-                Event::Window { win_event, .. } => match win_event {
-                    WindowEvent::Resized(width, height) => {
-                        render.update_viewport(0, 0, width, height)
-                    }
-                    WindowEvent::SizeChanged(width, height) => {
-                        render.update_viewport(0, 0, width, height)
-                    }
-                    _ => {}
-                },
-                _ => {}
-            }
-        }
+    match std::fs::write(&path, encode_bmp(width, height, pixels)) {
+        Ok(()) => println!("Screenshot saved to {path}"),
+        Err(error) => eprintln!("Can't save screenshot: {error}"),
+    }
+}
 
-        let delta = timer.elapsed().as_secs_f32();
-        timer = std::time::Instant::now();
+/// Uncompressed 32 bit BMP from RGBA pixels stored top row first
+fn encode_bmp(width: u32, height: u32, pixels: &[u8]) -> Vec<u8> {
+    const HEADER_SIZE: u32 = 14 + 40;
+    let image_size = width * height * 4;
 
-        render.update(&input_state, delta);
+    let mut bmp = Vec::with_capacity((HEADER_SIZE + image_size) as usize);
+    // File header
+    bmp.extend_from_slice(b"BM");
+    bmp.extend_from_slice(&(HEADER_SIZE + image_size).to_le_bytes());
+    bmp.extend_from_slice(&0u32.to_le_bytes());
+    bmp.extend_from_slice(&HEADER_SIZE.to_le_bytes());
+    // BITMAPINFOHEADER, a negative height stores rows top down
+    bmp.extend_from_slice(&40u32.to_le_bytes());
+    bmp.extend_from_slice(&(width as i32).to_le_bytes());
+    bmp.extend_from_slice(&(-(height as i32)).to_le_bytes());
+    bmp.extend_from_slice(&1u16.to_le_bytes());
+    bmp.extend_from_slice(&32u16.to_le_bytes());
+    bmp.extend_from_slice(&0u32.to_le_bytes()); // BI_RGB
+    bmp.extend_from_slice(&image_size.to_le_bytes());
+    bmp.extend_from_slice(&2835i32.to_le_bytes()); // 72 DPI
+    bmp.extend_from_slice(&2835i32.to_le_bytes());
+    bmp.extend_from_slice(&0u32.to_le_bytes());
+    bmp.extend_from_slice(&0u32.to_le_bytes());
 
-        render.render();
-        window.gl_swap_window();
-        ::std::thread::sleep(::std::time::Duration::new(0, 1_000_000_000u32 / FRAMERATE));
+    for pixel in pixels.chunks_exact(4) {
+        bmp.extend_from_slice(&[pixel[2], pixel[1], pixel[0], pixel[3]]);
     }
+
+    bmp
 }
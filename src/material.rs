@@ -1,19 +1,122 @@
-use crate::shader::Program;
+use crate::render::SceneSettings;
+use crate::shader::{Program, ShaderFeatures};
+use crate::texture::FilterPolicy;
+
+use cgmath::SquareMatrix;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AlphaMode {
+    Opaque,
+    Mask(f32),
+    Blend,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct TextureRef {
+    pub(crate) texture: usize,
+    pub(crate) tex_coord: u32,
+    pub(crate) transform: cgmath::Matrix3<f32>,
+}
+
+impl TextureRef {
+    pub fn new(texture: usize, tex_coord: u32) -> Self {
+        Self {
+            texture,
+            tex_coord,
+            transform: cgmath::Matrix3::identity(),
+        }
+    }
+
+    // KHR_texture_transform: translation * rotation * scale
+    pub fn with_transform(mut self, offset: [f32; 2], rotation: f32, scale: [f32; 2]) -> Self {
+        let (sin, cos) = rotation.sin_cos();
+
+        let translation =
+            cgmath::Matrix3::new(1.0, 0.0, 0.0, 0.0, 1.0, 0.0, offset[0], offset[1], 1.0);
+        let rotation = cgmath::Matrix3::new(cos, -sin, 0.0, sin, cos, 0.0, 0.0, 0.0, 1.0);
+        let scale = cgmath::Matrix3::new(scale[0], 0.0, 0.0, 0.0, scale[1], 0.0, 0.0, 0.0, 1.0);
+
+        self.transform = translation * rotation * scale;
+        self
+    }
+}
+
+/// Technique specific uniform value, read from `psx_parameters` material extras
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Parameter {
+    Float(f32),
+    Vec2(cgmath::Vector2<f32>),
+    Vec3(cgmath::Vector3<f32>),
+    Vec4(cgmath::Vector4<f32>),
+}
+
+impl Parameter {
+    /// Numbers become floats, arrays of 2 to 4 numbers become vectors
+    pub fn from_components(components: &[f32]) -> Option<Self> {
+        match *components {
+            [x] => Some(Self::Float(x)),
+            [x, y] => Some(Self::Vec2(cgmath::vec2(x, y))),
+            [x, y, z] => Some(Self::Vec3(cgmath::vec3(x, y, z))),
+            [x, y, z, w] => Some(Self::Vec4(cgmath::vec4(x, y, z, w))),
+            _ => None,
+        }
+    }
+
+    pub fn load(&self, program: &mut Program, name: &str) {
+        match *self {
+            Self::Float(value) => program.load_uniform_vec(name, cgmath::vec1(value)),
+            Self::Vec2(value) => program.load_uniform_vec(name, value),
+            Self::Vec3(value) => program.load_uniform_vec(name, value),
+            Self::Vec4(value) => program.load_uniform_vec(name, value),
+        }
+    }
+}
 
 #[derive(Debug)]
 pub struct Material {
-    pub(crate) shader: Option<usize>,
-    pub(crate) albedo: Option<usize>,
-    pub(crate) base_color: Option<cgmath::Vector3<f32>>,
+    /// Index in the technique registry, the default technique unless the material names one
+    pub(crate) technique: usize,
+    /// Uniforms of the technique beyond the glTF material model
+    pub(crate) parameters: Vec<(String, Parameter)>,
+
+    pub(crate) albedo: Option<TextureRef>,
+    pub(crate) base_color: cgmath::Vector4<f32>,
+
+    pub(crate) emissive: Option<TextureRef>,
+    pub(crate) emissive_color: cgmath::Vector3<f32>,
+
+    // Imported for completeness, PSX styled shaders don't use them. The maps are kept as
+    // metadata without uploading them, their references index the glTF textures.
+    pub(crate) normal: Option<TextureRef>,
+    pub(crate) normal_scale: f32,
+    pub(crate) metallic_roughness: Option<TextureRef>,
+    pub(crate) metallic: f32,
+    pub(crate) roughness: f32,
+
+    pub(crate) alpha_mode: AlphaMode,
+    pub(crate) double_sided: bool,
+    /// KHR_materials_unlit, never lit even if the scene has lighting
+    pub(crate) unlit: bool,
+
+    /// Overrides of the scene settings
+    pub(crate) filter_policy: Option<FilterPolicy>,
+    pub(crate) affine: Option<bool>,
+    pub(crate) dithered: Option<bool>,
 }
 
 impl Material {
-    // TODO: fix this crap
-    pub fn choose_shader(&self, _programs: &[Program]) -> usize {
-        if self.albedo.is_some() {
-            1
-        } else {
-            0
+    /// Features of the program permutation, geometry dependent ones are left unset
+    pub fn features(&self, settings: &SceneSettings) -> ShaderFeatures {
+        ShaderFeatures {
+            textured: self.albedo.is_some(),
+            emissive_mapped: self.emissive.is_some(),
+            vertex_colored: false,
+            lit: settings.lighting && !self.unlit,
+            alpha_tested: matches!(self.alpha_mode, AlphaMode::Mask(_)),
+            affine: self.affine.unwrap_or(settings.affine),
+            dithered: self.dithered.unwrap_or(settings.dither),
+            instanced: false,
+            fogged: settings.fog.is_some(),
         }
     }
 }
@@ -21,9 +124,23 @@ impl Material {
 impl Default for Material {
     fn default() -> Self {
         Self {
-            shader: None,
+            technique: 0,
+            parameters: Vec::new(),
             albedo: None,
-            base_color: Some(cgmath::vec3(0.5, 0.0, 0.2)),
+            base_color: cgmath::vec4(0.5, 0.0, 0.2, 1.0),
+            emissive: None,
+            emissive_color: cgmath::vec3(0.0, 0.0, 0.0),
+            normal: None,
+            normal_scale: 1.0,
+            metallic_roughness: None,
+            metallic: 1.0,
+            roughness: 1.0,
+            alpha_mode: AlphaMode::Opaque,
+            double_sided: false,
+            unlit: false,
+            filter_policy: None,
+            affine: None,
+            dithered: None,
         }
     }
 }
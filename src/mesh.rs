@@ -1,34 +1,46 @@
-use crate::buffer::*;
-use crate::vao::Vao;
+use crate::bounds::Aabb;
+use crate::geometry::GeometryRange;
 
-use crate::shader::vertex::{NORMAL_LOCATION, POSITION_LOCATION, TEXTURE_LOCATION};
-
-use cgmath::{Vector2, Vector3, Zero};
-
-const POS_VBO: usize = 0;
-const NOR_VBO: usize = 1;
-const TEX_VBO: usize = 2;
+use cgmath::{Vector2, Vector3, Vector4, Zero};
 
 #[derive(Debug)]
 pub struct Mesh(pub Vec<Primitive>);
 
+impl Mesh {
+    /// Bounds of all primitives in mesh space
+    pub(crate) fn bounds(&self) -> Aabb {
+        self.0.iter().fold(Aabb::empty(), |bounds, primitive| {
+            bounds.union(primitive.bounds())
+        })
+    }
+}
+
 impl Default for Mesh {
     fn default() -> Self {
         Self(vec![Primitive::default()])
     }
 }
 
+/// CPU copy of a primitive's vertex attributes and indices
+#[derive(Debug)]
+pub(crate) struct PrimitiveData {
+    pub vertices: Vec<Vector3<f32>>,
+    pub normals: Vec<Vector3<f32>>,
+    pub tex_coords: Vec<Vector2<f32>>,
+    pub tex_coords_1: Option<Vec<Vector2<f32>>>,
+    pub colors: Option<Vec<Vector4<f32>>>,
+    pub indices: Vec<u32>,
+}
+
+/// Drawn from the scene's geometry buffer, see `GeometryBuffer`
 #[derive(Debug)]
-#[repr(C)]
 pub struct Primitive {
-    vertices: Vec<Vector3<f32>>,
-    normals: Vec<Vector3<f32>>,
-    tex_coords: Vec<Vector2<f32>>,
-
-    indices: Vec<u32>,
-    vbos: [Vbo; 3],
-    ebo: Ebo,
-    vao: Vao,
+    /// None once released after the upload
+    data: Option<PrimitiveData>,
+    /// Mesh space bounds of the vertices, computed once at load
+    bounds: Aabb,
+    has_colors: bool,
+    pub(crate) range: GeometryRange,
 }
 
 impl Primitive {
@@ -36,48 +48,79 @@ impl Primitive {
         vertices: Vec<Vector3<f32>>,
         normals: Vec<Vector3<f32>>,
         tex_coords: Vec<Vector2<f32>>,
+        tex_coords_1: Option<Vec<Vector2<f32>>>,
+        colors: Option<Vec<Vector4<f32>>>,
         indices: Vec<u32>,
     ) -> Self {
-        let mut vao = Vao::new();
-        let mut vbos = [Vbo::new(), Vbo::new(), Vbo::new()];
-        let mut ebo = Ebo::new();
+        Self {
+            bounds: Aabb::from_points(&vertices),
+            has_colors: colors.is_some(),
+            data: Some(PrimitiveData {
+                vertices,
+                normals,
+                tex_coords,
+                tex_coords_1,
+                colors,
+                indices,
+            }),
+            range: GeometryRange::default(),
+        }
+    }
 
-        vao.as_context(|| {
-            vbos[POS_VBO].fill_with(bytemuck::cast_slice(vertices.as_slice()), DrawType::Static);
-            vbos[POS_VBO].set_attrib_ptr(POSITION_LOCATION, 3, ObjectType::Float, false);
+    pub(crate) fn bounds(&self) -> Aabb {
+        self.bounds
+    }
 
-            vbos[NOR_VBO].fill_with(bytemuck::cast_slice(normals.as_slice()), DrawType::Static);
-            vbos[NOR_VBO].set_attrib_ptr(NORMAL_LOCATION, 3, ObjectType::Float, false);
+    pub(crate) fn data(&self) -> Option<&PrimitiveData> {
+        self.data.as_ref()
+    }
 
-            vbos[TEX_VBO].fill_with(
-                bytemuck::cast_slice(tex_coords.as_slice()),
-                DrawType::Static,
-            );
-            vbos[TEX_VBO].set_attrib_ptr(TEXTURE_LOCATION, 2, ObjectType::Float, false);
+    /// Frees the CPU copy, `triangles` is empty afterwards
+    pub(crate) fn release_data(&mut self) {
+        self.data = None;
+    }
 
-            ebo.fill_with(bytemuck::cast_slice(indices.as_slice()), DrawType::Static);
-        });
+    pub(crate) fn triangles(&self) -> impl Iterator<Item = [Vector3<f32>; 3]> + '_ {
+        self.data.iter().flat_map(|data| {
+            data.indices.chunks_exact(3).map(|triangle| {
+                [
+                    data.vertices[triangle[0] as usize],
+                    data.vertices[triangle[1] as usize],
+                    data.vertices[triangle[2] as usize],
+                ]
+            })
+        })
+    }
 
-        Self {
-            vertices,
-            normals,
-            tex_coords,
-            indices,
-            vbos,
-            ebo,
-            vao,
+    pub(crate) fn has_colors(&self) -> bool {
+        self.has_colors
+    }
+
+    /// Draws `count` instances with the geometry buffer bound
+    pub(crate) fn draw_instanced(&self, count: usize) {
+        unsafe {
+            gl::DrawElementsInstancedBaseVertex(
+                gl::TRIANGLES,
+                self.range.index_count.try_into().unwrap(),
+                gl::UNSIGNED_INT,
+                (self.range.first_index * std::mem::size_of::<u32>()) as *const _,
+                count.try_into().unwrap(),
+                self.range.base_vertex,
+            );
         }
     }
 
-    pub(crate) fn render(&mut self) {
-        self.vao.as_context(|| unsafe {
-            gl::DrawElements(
+    /// Draws with the geometry buffer bound
+    pub(crate) fn draw(&self) {
+        unsafe {
+            gl::DrawElementsBaseVertex(
                 gl::TRIANGLES,
-                self.indices.len().try_into().unwrap(),
+                self.range.index_count.try_into().unwrap(),
                 gl::UNSIGNED_INT,
-                std::ptr::null(),
+                (self.range.first_index * std::mem::size_of::<u32>()) as *const _,
+                self.range.base_vertex,
             );
-        });
+        }
     }
 }
 
@@ -182,6 +225,6 @@ impl Default for Primitive {
             5, 6, 4, 7, 6, 4, 5,
         ];
 
-        Self::new(vertices, colors, tex_coords, indices)
+        Self::new(vertices, colors, tex_coords, None, None, indices)
     }
 }
@@ -0,0 +1,403 @@
+use std::path::Path;
+
+use crate::buffer::{Buffer, DrawType, ObjectType, Vbo};
+use crate::shader::fragment::ALBEDO_TEX;
+use crate::shader::vertex::{COLOR_LOCATION, POSITION_LOCATION, TEXTURE_LOCATION};
+use crate::shader::Program;
+use crate::texture::{PixelFormat, Texture2D};
+use crate::vao::Vao;
+use crate::GlObject;
+
+const VERTEX_PATH: &str = "shaders/overlay/vert.glsl";
+const FRAGMENT_PATH: &str = "shaders/overlay/frag.glsl";
+
+/// Position, texture coordinate and color
+const VERTEX_FLOATS: usize = 8;
+
+/// Screen area in render resolution pixels, the origin is the top left corner
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl Rect {
+    pub fn new(x: i32, y: i32, width: i32, height: i32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+}
+
+/// Image loaded into the overlay
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImageId(usize);
+
+/// Fixed-width font, glyphs are laid out row by row in an atlas image
+/// in character code order, like PS1 debug fonts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BitmapFont {
+    image: ImageId,
+    glyph_size: (i32, i32),
+    columns: i32,
+    first: char,
+    count: u32,
+}
+
+impl BitmapFont {
+    pub fn glyph_size(&self) -> (i32, i32) {
+        self.glyph_size
+    }
+
+    /// Width of the longest line and height of all lines in pixels
+    pub fn measure(&self, text: &str) -> (i32, i32) {
+        let (width, height) = self.glyph_size;
+        let lines = text.split('\n');
+        let columns = lines.clone().map(|line| line.chars().count()).max();
+
+        (
+            columns.unwrap_or_default() as i32 * width,
+            lines.count() as i32 * height,
+        )
+    }
+
+    /// Atlas area of the glyph, none for characters the font doesn't have
+    fn glyph(&self, character: char) -> Option<Rect> {
+        let index = (character as u32).checked_sub(self.first as u32)?;
+        if index >= self.count {
+            return None;
+        }
+
+        let (width, height) = self.glyph_size;
+        let index = index as i32;
+        Some(Rect::new(
+            index % self.columns * width,
+            index / self.columns * height,
+            width,
+            height,
+        ))
+    }
+}
+
+#[derive(Debug)]
+struct Image {
+    texture: Texture2D,
+    size: (u32, u32),
+}
+
+/// Consecutive quads sampling the same image
+#[derive(Debug, Clone, Copy)]
+struct Batch {
+    /// Boxes sample a white pixel
+    image: Option<ImageId>,
+    first: usize,
+    count: usize,
+}
+
+/// 2D layer drawn over the scene in the low resolution framebuffer: colored boxes,
+/// images and bitmap text for HUDs, menus and debug info.
+///
+/// Drawing is retained, everything added is drawn every frame until `clear`.
+/// Colors are display (sRGB) colors with alpha.
+#[derive(Debug)]
+pub struct Overlay {
+    images: Vec<Image>,
+    white: Texture2D,
+    vertices: Vec<f32>,
+    batches: Vec<Batch>,
+    /// Vertices changed since the last upload
+    dirty: bool,
+    /// Index in the program storage
+    program: usize,
+    vao: Vao,
+    vbo: Vbo,
+}
+
+impl Overlay {
+    pub(crate) fn new(programs: &mut Vec<Program>) -> Result<Self, String> {
+        let mut program = Program::from_files([
+            (VERTEX_PATH, gl::VERTEX_SHADER),
+            (FRAGMENT_PATH, gl::FRAGMENT_SHADER),
+        ])?;
+        program.bind_uniform_block(
+            crate::shader::block::FRAME_NAME,
+            crate::shader::block::FRAME_BINDING,
+        );
+        programs.push(program);
+
+        let mut vao = Vao::new();
+        let mut vbo = Vbo::new();
+        let stride = VERTEX_FLOATS * std::mem::size_of::<f32>();
+        vao.as_context(|| {
+            vbo.fill_with(&[], DrawType::Stream);
+            vbo.set_interleaved_attrib_ptr(
+                POSITION_LOCATION,
+                2,
+                ObjectType::Float,
+                false,
+                stride,
+                0,
+            );
+            vbo.set_interleaved_attrib_ptr(
+                TEXTURE_LOCATION,
+                2,
+                ObjectType::Float,
+                false,
+                stride,
+                8,
+            );
+            vbo.set_interleaved_attrib_ptr(COLOR_LOCATION, 4, ObjectType::Float, false, stride, 16);
+        });
+
+        Ok(Self {
+            images: Vec::new(),
+            white: image_texture(&[255; 4], (1, 1)),
+            vertices: Vec::new(),
+            batches: Vec::new(),
+            dirty: false,
+            program: programs.len() - 1,
+            vao,
+            vbo,
+        })
+    }
+
+    /// Loads a PNG image, transparent pixels aren't drawn
+    pub fn load_image<P: AsRef<Path>>(&mut self, path: P) -> Result<ImageId, String> {
+        let path = path.as_ref();
+        let image = image::open(path)
+            .map_err(|error| format!("{}: {error}", path.display()))?
+            .to_rgba8();
+        let size = image.dimensions();
+
+        Ok(self.add_image(image.as_raw(), size))
+    }
+
+    /// Adds an image from RGBA8 pixels stored top row first
+    pub fn add_image(&mut self, pixels: &[u8], size: (u32, u32)) -> ImageId {
+        assert_eq!(pixels.len(), (size.0 * size.1 * 4) as usize);
+
+        self.images.push(Image {
+            texture: image_texture(pixels, size),
+            size,
+        });
+        ImageId(self.images.len() - 1)
+    }
+
+    pub fn image_size(&self, image: ImageId) -> (u32, u32) {
+        self.images[image.0].size
+    }
+
+    /// Font of `glyph_size` glyphs filling the image's rows, starting with `first`
+    /// (usually ' ' for ASCII fonts)
+    pub fn font(&self, image: ImageId, glyph_size: (u32, u32), first: char) -> BitmapFont {
+        let (width, height) = self.image_size(image);
+        let columns = (width / glyph_size.0.max(1)).max(1);
+        let rows = height / glyph_size.1.max(1);
+
+        BitmapFont {
+            image,
+            glyph_size: (glyph_size.0 as i32, glyph_size.1 as i32),
+            columns: columns as i32,
+            first,
+            count: columns * rows,
+        }
+    }
+
+    /// Removes everything drawn
+    pub fn clear(&mut self) {
+        self.vertices.clear();
+        self.batches.clear();
+        self.dirty = true;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.batches.is_empty()
+    }
+
+    /// Draws a solid box
+    pub fn fill_rect(&mut self, rect: Rect, color: cgmath::Vector4<f32>) {
+        self.push_quad(None, rect, [0.0, 0.0, 1.0, 1.0], color);
+    }
+
+    /// Draws `source` pixels of the image, the whole image without one, stretched over `rect`
+    /// and multiplied by `tint`
+    pub fn draw_image(
+        &mut self,
+        image: ImageId,
+        rect: Rect,
+        source: Option<Rect>,
+        tint: cgmath::Vector4<f32>,
+    ) {
+        let (width, height) = self.image_size(image);
+        let source = source.unwrap_or(Rect::new(0, 0, width as i32, height as i32));
+        let tex_coords = [
+            source.x as f32 / width as f32,
+            source.y as f32 / height as f32,
+            (source.x + source.width) as f32 / width as f32,
+            (source.y + source.height) as f32 / height as f32,
+        ];
+
+        self.push_quad(Some(image), rect, tex_coords, tint);
+    }
+
+    /// Draws text starting at the top left `position`, `\n` starts a new line.
+    /// Characters the font doesn't have are left blank.
+    pub fn draw_text(
+        &mut self,
+        font: &BitmapFont,
+        position: (i32, i32),
+        text: &str,
+        color: cgmath::Vector4<f32>,
+    ) {
+        let (width, height) = font.glyph_size;
+        let (mut x, mut y) = position;
+
+        for character in text.chars() {
+            if character == '\n' {
+                x = position.0;
+                y += height;
+                continue;
+            }
+
+            if let Some(glyph) = font.glyph(character) {
+                self.draw_image(
+                    font.image,
+                    Rect::new(x, y, width, height),
+                    Some(glyph),
+                    color,
+                );
+            }
+            x += width;
+        }
+    }
+
+    fn push_quad(
+        &mut self,
+        image: Option<ImageId>,
+        rect: Rect,
+        [u0, v0, u1, v1]: [f32; 4],
+        color: cgmath::Vector4<f32>,
+    ) {
+        let (x0, y0) = (rect.x as f32, rect.y as f32);
+        let (x1, y1) = ((rect.x + rect.width) as f32, (rect.y + rect.height) as f32);
+        let first = self.vertices.len() / VERTEX_FLOATS;
+
+        for (x, y, u, v) in [
+            (x0, y0, u0, v0),
+            (x0, y1, u0, v1),
+            (x1, y1, u1, v1),
+            (x0, y0, u0, v0),
+            (x1, y1, u1, v1),
+            (x1, y0, u1, v0),
+        ] {
+            self.vertices
+                .extend_from_slice(&[x, y, u, v, color.x, color.y, color.z, color.w]);
+        }
+
+        match self.batches.last_mut() {
+            Some(batch) if batch.image == image => batch.count += 6,
+            _ => self.batches.push(Batch {
+                image,
+                first,
+                count: 6,
+            }),
+        }
+        self.dirty = true;
+    }
+
+    /// Draws over the bound framebuffer without depth, the Frame block has to be filled
+    pub(crate) fn render(&mut self, programs: &mut [Program]) {
+        if self.batches.is_empty() {
+            return;
+        }
+
+        if self.dirty {
+            self.vbo.fill_with(
+                bytemuck::cast_slice(self.vertices.as_slice()),
+                DrawType::Stream,
+            );
+            self.dirty = false;
+        }
+
+        let program = &mut programs[self.program];
+        program.load_uniform_vec("image", cgmath::vec1(ALBEDO_TEX as i32));
+        program.bind();
+        self.vao.bind();
+
+        unsafe {
+            gl::Disable(gl::DEPTH_TEST);
+            gl::Enable(gl::BLEND);
+            gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+            gl::ActiveTexture(gl::TEXTURE0 + ALBEDO_TEX);
+
+            for batch in self.batches.iter() {
+                let texture = match batch.image {
+                    Some(image) => &self.images[image.0].texture,
+                    None => &self.white,
+                };
+                gl::BindTexture(gl::TEXTURE_2D, texture.glid());
+                gl::DrawArrays(gl::TRIANGLES, batch.first as i32, batch.count as i32);
+            }
+
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+            gl::BindVertexArray(0);
+            gl::Disable(gl::BLEND);
+            gl::Enable(gl::DEPTH_TEST);
+        }
+    }
+}
+
+/// Nearest filtered texture, overlay pixels map to framebuffer pixels
+fn image_texture(pixels: &[u8], size: (u32, u32)) -> Texture2D {
+    Texture2D::new(
+        gl::CLAMP_TO_EDGE,
+        gl::CLAMP_TO_EDGE,
+        gl::NEAREST,
+        gl::NEAREST,
+        Some(pixels),
+        PixelFormat::new(gl::RGBA8, gl::RGBA, gl::UNSIGNED_BYTE),
+        size,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 128x48 atlas of 8x8 ASCII glyphs from ' '
+    const FONT: BitmapFont = BitmapFont {
+        image: ImageId(0),
+        glyph_size: (8, 8),
+        columns: 16,
+        first: ' ',
+        count: 96,
+    };
+
+    #[test]
+    fn glyphs_are_laid_out_row_by_row() {
+        assert_eq!(FONT.glyph(' '), Some(Rect::new(0, 0, 8, 8)));
+        assert_eq!(FONT.glyph('/'), Some(Rect::new(120, 0, 8, 8)));
+        assert_eq!(FONT.glyph('0'), Some(Rect::new(0, 8, 8, 8)));
+        assert_eq!(FONT.glyph('A'), Some(Rect::new(8, 16, 8, 8)));
+        assert_eq!(FONT.glyph('\u{7f}'), Some(Rect::new(120, 40, 8, 8)));
+    }
+
+    #[test]
+    fn missing_glyphs() {
+        assert_eq!(FONT.glyph('\t'), None);
+        assert_eq!(FONT.glyph('\u{80}'), None);
+        assert_eq!(FONT.glyph('é'), None);
+    }
+
+    #[test]
+    fn measures_the_longest_line() {
+        assert_eq!(FONT.measure("ab\ncde"), (24, 16));
+        assert_eq!(FONT.measure("abc\n"), (24, 16));
+        assert_eq!(FONT.measure(""), (0, 8));
+    }
+}
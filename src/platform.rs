@@ -0,0 +1,161 @@
+use std::collections::VecDeque;
+use std::ffi::c_void;
+
+#[cfg(feature = "sdl")]
+pub mod sdl;
+
+/// Game controller stick axes, values from -1 to 1 with y pointing down
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StickAxis {
+    LeftX,
+    LeftY,
+    RightX,
+    RightY,
+}
+
+/// Host independent window and input events.
+/// Keys and buttons are named like in the bindings file.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PlatformEvent {
+    Quit,
+    /// New drawable size in pixels
+    Resized(u32, u32),
+    KeyDown(String),
+    KeyUp(String),
+    ButtonDown(String),
+    ButtonUp(String),
+    StickMoved(StickAxis, f32),
+    /// Name of a newly connected game controller
+    ControllerAdded(String),
+    /// A game controller was connected but couldn't be opened
+    ControllerFailed(String),
+    /// Stick positions are no longer valid
+    ControllerRemoved,
+    MouseMotion(i32, i32),
+    MouseWheel(i32),
+}
+
+/// How buffer swaps synchronize with the display refresh
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum SwapInterval {
+    Immediate,
+    VSync,
+    /// Synchronized, but late frames are shown immediately and tear
+    #[default]
+    Adaptive,
+}
+
+/// Host of the renderer: owns the window and the current GL context
+pub trait Platform {
+    /// Address of a GL function of the platform's context, null if unavailable
+    fn gl_proc_address(&self, name: &str) -> *const c_void;
+
+    /// Next pending event, None once the queue is drained for this frame
+    fn poll_event(&mut self) -> Option<PlatformEvent>;
+
+    fn swap_buffers(&mut self);
+
+    fn set_swap_interval(&mut self, interval: SwapInterval) -> Result<(), String>;
+
+    /// Seconds since the platform was created
+    fn time(&self) -> f64;
+
+    /// Blocks for the given seconds, used for frame pacing
+    fn sleep(&mut self, seconds: f64) {
+        std::thread::sleep(std::time::Duration::from_secs_f64(seconds));
+    }
+
+    /// Drawable size in pixels
+    fn size(&self) -> (u32, u32);
+}
+
+/// Loads GL function pointers from the platform's context
+pub fn load_gl(platform: &impl Platform) {
+    gl::load_with(|name| platform.gl_proc_address(name));
+}
+
+type ProcLoader = Box<dyn Fn(&str) -> *const c_void>;
+
+/// Platform without a window for test harnesses and offscreen rendering.
+///
+/// The GL context is created and made current by the embedder (e.g. an EGL
+/// pbuffer), `loader` resolves its functions. Time is simulated and advances by
+/// a fixed step on every swap so runs are reproducible, input is scripted with
+/// `push_event`. Sleeping advances the simulated time without blocking.
+pub struct Headless {
+    loader: ProcLoader,
+    events: VecDeque<PlatformEvent>,
+    size: (u32, u32),
+    time: f64,
+    step: f64,
+    frames: u64,
+}
+
+impl Headless {
+    pub fn new(
+        size: (u32, u32),
+        step: f64,
+        loader: impl Fn(&str) -> *const c_void + 'static,
+    ) -> Self {
+        Self {
+            loader: Box::new(loader),
+            events: VecDeque::new(),
+            size,
+            time: 0.0,
+            step,
+            frames: 0,
+        }
+    }
+
+    pub fn push_event(&mut self, event: PlatformEvent) {
+        self.events.push_back(event);
+    }
+
+    /// Number of presented frames
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+}
+
+impl std::fmt::Debug for Headless {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Headless")
+            .field("events", &self.events)
+            .field("size", &self.size)
+            .field("time", &self.time)
+            .field("step", &self.step)
+            .field("frames", &self.frames)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Platform for Headless {
+    fn gl_proc_address(&self, name: &str) -> *const c_void {
+        (self.loader)(name)
+    }
+
+    fn poll_event(&mut self) -> Option<PlatformEvent> {
+        self.events.pop_front()
+    }
+
+    fn swap_buffers(&mut self) {
+        self.frames += 1;
+        self.time += self.step;
+    }
+
+    fn set_swap_interval(&mut self, _interval: SwapInterval) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn time(&self) -> f64 {
+        self.time
+    }
+
+    fn sleep(&mut self, seconds: f64) {
+        self.time += seconds;
+    }
+
+    fn size(&self) -> (u32, u32) {
+        self.size
+    }
+}
@@ -0,0 +1,159 @@
+use std::ffi::c_void;
+use std::time::Instant;
+
+use sdl2::controller::{Axis, GameController};
+use sdl2::event::{Event, WindowEvent};
+use sdl2::video::{GLContext, GLProfile, Window};
+use sdl2::{EventPump, GameControllerSubsystem, Sdl, VideoSubsystem};
+
+use super::{Platform, PlatformEvent, StickAxis, SwapInterval};
+
+const GL_MAJOR_VERSION: u8 = 3;
+const GL_MINOR_VERSION: u8 = 3;
+
+/// Resizable window with a GL 3.3 core context, grabbed relative mouse and game controllers
+pub struct SdlPlatform {
+    _sdl: Sdl,
+    video: VideoSubsystem,
+    window: Window,
+    _gl_context: GLContext,
+    event_pump: EventPump,
+    controller_subsystem: GameControllerSubsystem,
+    /// Opened controllers have to be kept alive to receive their events
+    controllers: Vec<GameController>,
+    start: Instant,
+}
+
+impl SdlPlatform {
+    pub fn new(title: &str, width: u32, height: u32) -> Result<Self, String> {
+        let sdl = sdl2::init()?;
+        let video = sdl.video()?;
+
+        let gl_attr = video.gl_attr();
+        gl_attr.set_context_profile(GLProfile::Core);
+        gl_attr.set_context_version(GL_MAJOR_VERSION, GL_MINOR_VERSION);
+
+        let mut window = video
+            .window(title, width, height)
+            .opengl()
+            .resizable()
+            .build()
+            .map_err(|error| error.to_string())?;
+
+        window.set_grab(true);
+        sdl.mouse().set_relative_mouse_mode(true);
+
+        let gl_context = window.gl_create_context()?;
+
+        debug_assert_eq!(gl_attr.context_profile(), GLProfile::Core);
+        debug_assert_eq!(
+            gl_attr.context_version(),
+            (GL_MAJOR_VERSION, GL_MINOR_VERSION)
+        );
+
+        Ok(Self {
+            event_pump: sdl.event_pump()?,
+            controller_subsystem: sdl.game_controller()?,
+            controllers: Vec::new(),
+            _sdl: sdl,
+            video,
+            window,
+            _gl_context: gl_context,
+            start: Instant::now(),
+        })
+    }
+
+    fn convert(&mut self, event: Event) -> Option<PlatformEvent> {
+        let event = match event {
+            Event::Quit { .. } => PlatformEvent::Quit,
+            // The event has the size in screen coordinates, HiDPI drawables are larger
+            Event::Window {
+                win_event: WindowEvent::SizeChanged(..),
+                ..
+            } => {
+                let (width, height) = self.window.drawable_size();
+                PlatformEvent::Resized(width, height)
+            }
+            Event::KeyDown {
+                scancode: Some(scancode),
+                repeat: false,
+                ..
+            } => PlatformEvent::KeyDown(scancode.name().to_string()),
+            Event::KeyUp {
+                scancode: Some(scancode),
+                ..
+            } => PlatformEvent::KeyUp(scancode.name().to_string()),
+            Event::ControllerButtonDown { button, .. } => {
+                PlatformEvent::ButtonDown(button.string())
+            }
+            Event::ControllerButtonUp { button, .. } => PlatformEvent::ButtonUp(button.string()),
+            Event::ControllerAxisMotion { axis, value, .. } => {
+                let axis = match axis {
+                    Axis::LeftX => StickAxis::LeftX,
+                    Axis::LeftY => StickAxis::LeftY,
+                    Axis::RightX => StickAxis::RightX,
+                    Axis::RightY => StickAxis::RightY,
+                    Axis::TriggerLeft | Axis::TriggerRight => return None,
+                };
+                PlatformEvent::StickMoved(axis, value as f32 / i16::MAX as f32)
+            }
+            Event::ControllerDeviceAdded { which, .. } => {
+                match self.controller_subsystem.open(which) {
+                    Ok(controller) => {
+                        let name = controller.name();
+                        self.controllers.push(controller);
+                        PlatformEvent::ControllerAdded(name)
+                    }
+                    Err(error) => PlatformEvent::ControllerFailed(error.to_string()),
+                }
+            }
+            Event::ControllerDeviceRemoved { which, .. } => {
+                self.controllers
+                    .retain(|controller| controller.instance_id() != which);
+                PlatformEvent::ControllerRemoved
+            }
+            Event::MouseMotion { xrel, yrel, .. } => PlatformEvent::MouseMotion(xrel, yrel),
+            Event::MouseWheel { y, .. } => PlatformEvent::MouseWheel(y),
+            _ => return None,
+        };
+
+        Some(event)
+    }
+}
+
+impl Platform for SdlPlatform {
+    fn gl_proc_address(&self, name: &str) -> *const c_void {
+        self.video.gl_get_proc_address(name) as *const c_void
+    }
+
+    fn poll_event(&mut self) -> Option<PlatformEvent> {
+        // Skips events that have no platform independent counterpart
+        while let Some(event) = self.event_pump.poll_event() {
+            if let Some(event) = self.convert(event) {
+                return Some(event);
+            }
+        }
+
+        None
+    }
+
+    fn swap_buffers(&mut self) {
+        self.window.gl_swap_window();
+    }
+
+    fn set_swap_interval(&mut self, interval: SwapInterval) -> Result<(), String> {
+        self.video.gl_set_swap_interval(match interval {
+            SwapInterval::Immediate => sdl2::video::SwapInterval::Immediate,
+            SwapInterval::VSync => sdl2::video::SwapInterval::VSync,
+            SwapInterval::Adaptive => sdl2::video::SwapInterval::LateSwapTearing,
+        })
+    }
+
+    fn time(&self) -> f64 {
+        self.start.elapsed().as_secs_f64()
+    }
+
+    fn size(&self) -> (u32, u32) {
+        self.window.drawable_size()
+    }
+}
@@ -1,9 +1,28 @@
-use crate::camera::Camera;
+use std::collections::HashMap;
 
-use crate::material::Material;
-use crate::mesh::Mesh;
-use crate::shader::Program;
-use crate::texture::Texture2D;
+use crate::bounds::{Aabb, Frustum};
+use crate::buffer::{Buffer, DrawType, Ubo, Vbo};
+use crate::camera::{Camera, CameraMode, CameraPose, MOUSE_SENSIVITY, WORLD_UP, Z_FAR, Z_NEAR};
+use crate::camera_path::{CameraPath, Playback, Recorder};
+use crate::collision::CollisionMesh;
+use crate::fixed_camera::FixedCameras;
+use crate::framebuffer::FramebufferError;
+use crate::geometry::{GeometryBuffer, VertexFormat};
+use crate::gltf::TextureStats;
+use crate::input::{Action, InputState};
+use crate::lod::LodGroup;
+
+use crate::material::{AlphaMode, Material, TextureRef};
+use crate::mesh::{Mesh, Primitive};
+use crate::overlay::Overlay;
+use crate::render_queue::{DrawItem, GlState, RenderQueue, SortKey};
+use crate::shader::{Program, ShaderWatcher};
+use crate::sky::{SkyRenderer, SkyTexture};
+use crate::technique::{TechniqueRegistry, MANIFEST_PATH};
+use crate::texture::{FilterPolicy, Texture2D};
+use crate::walk::{Walker, WALK_SPEED};
+
+use cgmath::{InnerSpace, Matrix, SquareMatrix};
 
 #[derive(Debug)]
 pub struct AssetStorage {
@@ -11,55 +30,286 @@ pub struct AssetStorage {
     pub(crate) materials: Vec<Material>,
     pub(crate) textures2d: Vec<Texture2D>,
     pub(crate) programs: Vec<Program>,
+    pub(crate) techniques: TechniqueRegistry,
+    pub(crate) sky: Option<SkyTexture>,
 }
 
 #[derive(Debug, Clone)]
 pub struct Node {
     pub(crate) mesh: usize,
     pub(crate) materials: Vec<usize>,
+    /// Program permutation of every primitive
+    pub(crate) programs: Vec<usize>,
+    /// World space bounds of every primitive, for culling
+    pub(crate) bounds: Vec<Aabb>,
+    /// Nodes in a group are drawn instanced instead of one by one
+    pub(crate) instance_group: Option<usize>,
+    /// LOD group and level, drawn only while the level is selected
+    pub(crate) lod: Option<(usize, usize)>,
     pub(crate) transform: cgmath::Matrix4<f32>,
 }
 
+/// Per-frame data shared by all programs through the `Frame` uniform block (std140)
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct FrameUniforms {
+    view_projection: cgmath::Matrix4<f32>,
+    camera_position: cgmath::Vector4<f32>,
+    ambient_color: cgmath::Vector4<f32>,
+    light_direction: cgmath::Vector4<f32>,
+    light_color: cgmath::Vector4<f32>,
+    render_resolution: cgmath::Vector2<f32>,
+    _padding: cgmath::Vector2<f32>,
+    position_scale: cgmath::Vector4<f32>,
+    position_offset: cgmath::Vector4<f32>,
+    fog_color: cgmath::Vector4<f32>,
+    /// Start and end distance, mode and density
+    fog_range: cgmath::Vector4<f32>,
+}
+
+unsafe impl bytemuck::Zeroable for FrameUniforms {}
+unsafe impl bytemuck::Pod for FrameUniforms {}
+
+/// Per-instance vertex attributes of instanced draws
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct InstanceData {
+    model: cgmath::Matrix4<f32>,
+    normal_matrix: cgmath::Matrix3<f32>,
+}
+
+unsafe impl bytemuck::Zeroable for InstanceData {}
+unsafe impl bytemuck::Pod for InstanceData {}
+
+/// Per-scene options read from the scene extras
+#[derive(Debug, Default, Clone)]
+pub struct SceneSettings {
+    pub filter_policy: FilterPolicy,
+    /// Per-vertex lighting by the scene ambient and sun light
+    pub lighting: bool,
+    pub affine: bool,
+    pub dither: bool,
+    pub vertex_format: VertexFormat,
+    /// Frees CPU copies of the geometry once uploaded and the collision mesh is built
+    pub release_geometry: bool,
+    /// Primitives entirely farther from the camera aren't drawn
+    pub draw_distance: Option<f32>,
+    pub fog: Option<Fog>,
+    /// Display (sRGB) background color, the fog color replaces it
+    pub clear_color: Option<cgmath::Vector3<f32>>,
+    pub ambient_color: Option<cgmath::Vector3<f32>>,
+    /// Direction the sun light travels in, towards the ground
+    pub sun_direction: Option<cgmath::Vector3<f32>>,
+    pub sun_color: Option<cgmath::Vector3<f32>>,
+}
+
+/// Depth cueing: the blend factor is computed per vertex from the camera distance
+/// and interpolated, each fragment's final color is blended towards the fog color
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Fog {
+    /// Display (sRGB) color, blended after the shading
+    pub color: cgmath::Vector3<f32>,
+    pub mode: FogMode,
+    /// Distance from the camera where the blend starts
+    pub start: f32,
+    /// Distance where only the far color remains
+    pub end: f32,
+    /// Falloff of the exponential modes, by default the fog is opaque
+    /// within 8 bit precision at `end`
+    pub density: Option<f32>,
+}
+
+impl Fog {
+    fn density(&self) -> f32 {
+        // exp(-x) is 1/256 at x = ln(256)
+        let opaque = 256f32.ln();
+        let range = (self.end - self.start).max(f32::EPSILON);
+
+        self.density.unwrap_or(match self.mode {
+            FogMode::Linear => 0.0,
+            FogMode::Exponential => opaque / range,
+            FogMode::ExponentialSquared => opaque.sqrt() / range,
+        })
+    }
+}
+
+/// Falloff of the fog with distance, the discriminant is the shader mode
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum FogMode {
+    #[default]
+    Linear,
+    Exponential,
+    ExponentialSquared,
+}
+
+impl FogMode {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "linear" => Some(FogMode::Linear),
+            "exponential" => Some(FogMode::Exponential),
+            "exponential_squared" => Some(FogMode::ExponentialSquared),
+            _ => None,
+        }
+    }
+}
+
+/// Nodes sharing a mesh and materials, each primitive is drawn with one instanced call
+#[derive(Debug)]
+struct InstanceGroup {
+    nodes: Vec<usize>,
+    /// Instanced program permutation of every primitive
+    programs: Vec<usize>,
+}
+
+/// Meshes placed fewer times are drawn one by one
+const MIN_INSTANCES: usize = 2;
+
+/// Outcome of a shader hot reload
+#[derive(Debug, Default, Clone)]
+pub struct ShaderReload {
+    /// Files changed since the previous reload
+    pub changed: Vec<std::path::PathBuf>,
+    /// Programs relinked with the changes
+    pub relinked: usize,
+    /// Logs of the programs that failed to build, they keep their previous version
+    pub errors: Vec<String>,
+}
+
+/// Counts of the last rendered frame
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RenderStats {
+    /// Primitives drawn
+    pub drawn: usize,
+    /// Primitives outside the view frustum or beyond the draw distance
+    pub culled: usize,
+    pub draw_calls: usize,
+    /// Program, vertex array, texture and fixed function state changes
+    pub state_changes: usize,
+}
+
+/// What loading the scene built
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct LoadStats {
+    pub textures: TextureStats,
+    pub shader_permutations: usize,
+    /// Nodes drawn with instanced draw calls
+    pub instanced_nodes: usize,
+    pub instance_groups: usize,
+    pub vertices: usize,
+    /// Size of the vertex buffer
+    pub geometry_bytes: usize,
+    pub vertex_format: VertexFormat,
+    pub collision_triangles: usize,
+}
+
 #[derive(Debug)]
 pub struct World {
     assets: AssetStorage,
     nodes: Vec<Node>,
     camera: Camera,
     screen: (i32, i32, i32, i32),
+    filter_policy: FilterPolicy,
+    frame_uniforms: Ubo,
+    /// Watches `shaders/` for hot reloading, on by default in debug builds
+    shader_watcher: Option<ShaderWatcher>,
+    fixed_cameras: FixedCameras,
+    recorder: Option<Recorder>,
+    playback: Option<Playback>,
+    /// Scene triangles the walk mode collides with
+    collision: CollisionMesh,
+    walker: Walker,
+    /// Look stick turn rate, radians per second
+    look_speed: f32,
+    /// Camera before the last update, rendering blends from it
+    previous_pose: Option<CameraPose>,
+    stats: RenderStats,
+    load_stats: LoadStats,
+    queue: RenderQueue,
+    instance_groups: Vec<InstanceGroup>,
+    /// Transforms of the visible instances of the frame, uploaded to `instance_buffer`
+    instances: Vec<InstanceData>,
+    instance_buffer: Vbo,
+    /// Vertices and indices of every primitive
+    geometry: GeometryBuffer,
+    lod_groups: Vec<LodGroup>,
+    draw_distance: Option<f32>,
+    fog: Option<Fog>,
+    clear_color: cgmath::Vector3<f32>,
+    ambient_color: cgmath::Vector3<f32>,
+    sun_direction: cgmath::Vector3<f32>,
+    sun_color: cgmath::Vector3<f32>,
+    sky: Option<SkyRenderer>,
+    overlay: Overlay,
 }
 
+const SHADERS_PATH: &str = "shaders";
+const DEFAULT_LOOK_SPEED: f32 = 3.0;
+
+/// Lighting of scenes without their own
+const AMBIENT_COLOR: [f32; 3] = [0.35, 0.35, 0.4];
+const SUN_DIRECTION: [f32; 3] = [-0.4, -1.0, -0.3];
+const SUN_COLOR: [f32; 3] = [0.8, 0.75, 0.65];
+const CLEAR_COLOR: [f32; 3] = [0.6, 0.0, 0.8];
+
 const BASE_RENDER_WIDTH: u32 = 320;
 const BASE_RENDER_HEIGHT: u32 = 240;
 const BASE_PIXEL_COUNT: u32 = BASE_RENDER_WIDTH * BASE_RENDER_HEIGHT;
 
 impl World {
-    pub fn from_gltf_file<P: AsRef<std::path::Path>>(path: P) -> Self {
-        let vert_shader =
-            crate::shader::Shader::from_file("shaders/color/vert.glsl", gl::VERTEX_SHADER).unwrap();
-        let frag_shader =
-            crate::shader::Shader::from_file("shaders/color/frag.glsl", gl::FRAGMENT_SHADER)
-                .unwrap();
-        let shader_color =
-            crate::shader::Program::from_shaders([vert_shader, frag_shader]).unwrap();
-
-        let vert_shader =
-            crate::shader::Shader::from_file("shaders/texture/vert.glsl", gl::VERTEX_SHADER)
-                .unwrap();
-        let frag_shader =
-            crate::shader::Shader::from_file("shaders/texture/frag.glsl", gl::FRAGMENT_SHADER)
-                .unwrap();
-        let shader_texture =
-            crate::shader::Program::from_shaders([vert_shader, frag_shader]).unwrap();
-
-        let (mut storage, nodes) = crate::gltf::read_from_file(path);
-        storage.programs = vec![shader_color, shader_texture];
-
-        for material in storage.materials.iter_mut() {
-            let shader = material.choose_shader(&storage.programs);
-            material.shader = Some(shader);
-        }
-
-        World {
+    /// Loads the scene and builds its programs, fails on unsupported files and shaders
+    /// that don't compile
+    pub fn from_gltf_file<P: AsRef<std::path::Path>>(path: P) -> Result<Self, String> {
+        let techniques = TechniqueRegistry::from_manifest(MANIFEST_PATH)?;
+        let (mut storage, mut nodes, settings, fixed_cameras, mut lod_groups, textures) =
+            crate::gltf::read_from_file(path, techniques)?;
+
+        for node in nodes.iter_mut() {
+            let mesh = &storage.meshes[node.mesh];
+
+            node.bounds = mesh
+                .0
+                .iter()
+                .map(|primitive| primitive.bounds().transform(node.transform))
+                .collect();
+
+            let colored: Vec<_> = mesh.0.iter().map(Primitive::has_colors).collect();
+            node.programs = colored
+                .into_iter()
+                .zip(node.materials.iter())
+                .map(|(colored, &material)| {
+                    build_program(&mut storage, &settings, material, colored, false)
+                })
+                .collect::<Result<_, _>>()?;
+        }
+
+        for group in lod_groups.iter_mut() {
+            group.bounds = nodes[group.levels()[0]]
+                .bounds
+                .iter()
+                .fold(Aabb::empty(), |bounds, &primitive| bounds.union(primitive));
+        }
+
+        let instance_groups = group_instances(&mut storage, &settings, &mut nodes)?;
+        let sky = storage
+            .sky
+            .as_ref()
+            .map(|texture| SkyRenderer::new(texture, settings.fog.is_some(), &mut storage.programs))
+            .transpose()?;
+        let overlay = Overlay::new(&mut storage.programs)?;
+
+        let geometry = GeometryBuffer::new(&mut storage.meshes, settings.vertex_format);
+        let load_stats = LoadStats {
+            textures,
+            shader_permutations: storage.techniques.permutation_count(),
+            instanced_nodes: instance_groups.iter().map(|group| group.nodes.len()).sum(),
+            instance_groups: instance_groups.len(),
+            vertices: geometry.vertex_count(),
+            geometry_bytes: geometry.size(),
+            vertex_format: geometry.format(),
+            collision_triangles: 0,
+        };
+
+        let mut world = World {
             assets: storage,
             nodes,
             camera: Camera::new(
@@ -69,77 +319,272 @@ impl World {
                 (BASE_RENDER_WIDTH, BASE_RENDER_HEIGHT),
             ),
             screen: (0, 0, 800, 600),
+            filter_policy: settings.filter_policy,
+            frame_uniforms: Ubo::new(),
+            shader_watcher: cfg!(debug_assertions).then(|| ShaderWatcher::new(SHADERS_PATH)),
+            fixed_cameras,
+            recorder: None,
+            playback: None,
+            collision: CollisionMesh::default(),
+            walker: Walker::default(),
+            look_speed: DEFAULT_LOOK_SPEED,
+            previous_pose: None,
+            stats: RenderStats::default(),
+            load_stats,
+            queue: RenderQueue::default(),
+            instance_groups,
+            instances: Vec::new(),
+            instance_buffer: Vbo::new(),
+            geometry,
+            lod_groups,
+            draw_distance: settings.draw_distance,
+            fog: settings.fog,
+            // Geometry fades into the background
+            clear_color: match settings.fog {
+                Some(fog) => fog.color,
+                None => settings.clear_color.unwrap_or(CLEAR_COLOR.into()),
+            },
+            ambient_color: settings.ambient_color.unwrap_or(AMBIENT_COLOR.into()),
+            sun_direction: settings
+                .sun_direction
+                .filter(|direction| direction.magnitude2() > 0.0)
+                .unwrap_or(SUN_DIRECTION.into())
+                .normalize(),
+            sun_color: settings.sun_color.unwrap_or(SUN_COLOR.into()),
+            sky,
+            overlay,
+        };
+
+        // Large maps don't fit into the default far plane
+        let diameter = 2.0 * world.scene_bounds().radius();
+        if diameter > Z_FAR {
+            world.camera.set_clip_planes(Z_NEAR, Some(diameter));
+        }
+
+        // Less detailed levels would duplicate the triangles
+        let collided = world
+            .nodes
+            .iter()
+            .filter(|node| node.lod.is_none_or(|(_, level)| level == 0));
+        world.collision = CollisionMesh::new(collided.flat_map(|node| {
+            world.assets.meshes[node.mesh]
+                .0
+                .iter()
+                .flat_map(|primitive| primitive.triangles())
+                .map(move |triangle| {
+                    triangle.map(|vertex| (node.transform * vertex.extend(1.0)).truncate())
+                })
+        }));
+        world.load_stats.collision_triangles = world.collision.triangle_count();
+
+        if settings.release_geometry {
+            for mesh in world.assets.meshes.iter_mut() {
+                mesh.0.iter_mut().for_each(Primitive::release_data);
+            }
         }
+
+        Ok(world)
+    }
+
+    /// Renders the camera `alpha` of the way from the previous to the current update,
+    /// smooths motion when updates run at a fixed rate
+    pub fn render_interpolated(&mut self, alpha: f32) {
+        let pose = match self.previous_pose {
+            Some(previous) if alpha < 1.0 => Some(previous.lerp(self.camera.pose(), alpha)),
+            _ => None,
+        };
+        self.render_from(pose);
     }
 
     pub fn render(&mut self) {
-        let view = self.camera.view();
-        let projection = self.camera.projection();
+        self.render_from(None);
+    }
+
+    /// Renders from `pose`, or from the camera without one
+    fn render_from(&mut self, pose: Option<CameraPose>) {
+        let (view, projection, eye) = self.view_projection(pose);
         let dimensions = self.camera.dimensions;
+        let reversed_depth = self.camera.reversed_depth();
+        let clear_color = self.clear_color;
         let view_projection = projection * view;
+        let frustum = Frustum::from_matrix(view_projection);
+        let mut stats = RenderStats::default();
 
-        self.camera
-            .framebuffer
-            .as_context(|| {
-                unsafe {
-                    // gl::Disable(gl::DITHER);
-                    gl::Viewport(0, 0, dimensions.0 as i32, dimensions.1 as i32);
-                    gl::ClearColor(0.6, 0.0, 0.8, 1.0);
-                    gl::Enable(gl::DEPTH_TEST);
-                    gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+        for group in self.lod_groups.iter_mut() {
+            group.select(eye, projection);
+        }
+        self.fill_queue(&frustum, eye, &mut stats);
+        let (position_scale, position_offset) = self.geometry.position_transform();
+
+        let frame = FrameUniforms {
+            view_projection,
+            camera_position: eye.extend(1.0),
+            ambient_color: self.ambient_color.extend(1.0),
+            light_direction: self.sun_direction.extend(0.0),
+            light_color: self.sun_color.extend(1.0),
+            render_resolution: cgmath::vec2(dimensions.0 as f32, dimensions.1 as f32),
+            _padding: cgmath::vec2(0.0, 0.0),
+            position_scale: position_scale.extend(0.0),
+            position_offset: position_offset.extend(0.0),
+            fog_color: self
+                .fog
+                .map_or(cgmath::vec3(0.0, 0.0, 0.0), |fog| fog.color)
+                .extend(1.0),
+            fog_range: self.fog.map_or(cgmath::vec4(0.0, 0.0, 0.0, 0.0), |fog| {
+                cgmath::vec4(fog.start, fog.end, fog.mode as u32 as f32, fog.density())
+            }),
+        };
+        self.frame_uniforms
+            .fill_with(bytemuck::bytes_of(&frame), DrawType::Stream);
+        self.frame_uniforms
+            .bind_base(crate::shader::block::FRAME_BINDING);
+
+        self.camera.framebuffer.as_context(|| {
+            unsafe {
+                // gl::Disable(gl::DITHER);
+                gl::Viewport(0, 0, dimensions.0 as i32, dimensions.1 as i32);
+                gl::ClearColor(clear_color.x, clear_color.y, clear_color.z, 1.0);
+                gl::Enable(gl::DEPTH_TEST);
+                if reversed_depth {
+                    gl::ClearDepth(0.0);
+                    gl::DepthFunc(gl::GREATER);
+                } else {
+                    gl::ClearDepth(1.0);
+                    gl::DepthFunc(gl::LESS);
                 }
+                gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+            }
+
+            if let (Some(sky), Some(texture)) = (&self.sky, &self.assets.sky) {
+                sky.render(
+                    texture,
+                    &mut self.assets.programs,
+                    view,
+                    projection,
+                    self.camera.clip_planes(),
+                );
+            }
 
-                for node in self.nodes.iter_mut() {
-                    let mesh = &mut self.assets.meshes[node.mesh];
+            let mut state = GlState::default();
+            for item in self.queue.items() {
+                let node = &self.nodes[item.node];
+                let primitive = &self.assets.meshes[node.mesh].0[item.primitive];
+                let material_index = node.materials[item.primitive];
+                let material = &self.assets.materials[material_index];
+                let program_index = match item.instances {
+                    Some(_) => {
+                        let group = node.instance_group.unwrap();
+                        self.instance_groups[group].programs[item.primitive]
+                    }
+                    None => node.programs[item.primitive],
+                };
+                let program = &mut self.assets.programs[program_index];
 
-                    for (i, primitive) in mesh.0.iter_mut().enumerate() {
-                        let material = &mut self.assets.materials[node.materials[i]];
-                        let program = &mut self.assets.programs[material.shader.unwrap_or(0)];
+                let filter_policy = material.filter_policy.unwrap_or(self.filter_policy);
 
-                        let mvp = view_projection * node.transform;
+                state.use_program(program_index, program);
+                state.set_cull_face(!material.double_sided);
+                state.set_clockwise(node.transform.determinant() < 0.0);
+                state.set_blend(material.alpha_mode == AlphaMode::Blend);
+                state.bind_geometry(&self.geometry);
+
+                if item.instances.is_none() {
+                    program.load_uniform_mat("model", false, node.transform);
+                    if program.has_uniform("normalMatrix") {
+                        program.load_uniform_mat(
+                            "normalMatrix",
+                            false,
+                            normal_matrix(node.transform),
+                        );
+                    }
+                }
+
+                if state.use_material(material_index) {
+                    program.load_uniform_vec("baseColor", material.base_color);
+                    program.load_uniform_vec("emissiveColor", material.emissive_color);
+                    if let AlphaMode::Mask(cutoff) = material.alpha_mode {
+                        program.load_uniform_vec("alphaCutoff", cgmath::vec1(cutoff));
+                    }
 
-                        program.load_uniform_mat("mvp", false, mvp);
+                    if let Some(albedo) = material.albedo {
+                        load_texture_ref(
+                            program,
+                            &mut state,
+                            &self.assets.textures2d,
+                            albedo,
+                            "albedo",
+                            crate::shader::fragment::ALBEDO_TEX,
+                            filter_policy,
+                        );
+                    }
+                    if let Some(emissive) = material.emissive {
+                        load_texture_ref(
+                            program,
+                            &mut state,
+                            &self.assets.textures2d,
+                            emissive,
+                            "emissive",
+                            crate::shader::fragment::EMISSIVE_TEX,
+                            filter_policy,
+                        );
+                    }
+                    if material.albedo.is_some() || material.emissive.is_some() {
                         program.load_uniform_vec(
-                            "renderResolution",
-                            cgmath::vec2(dimensions.0 as f32, dimensions.1 as f32),
+                            "threePoint",
+                            cgmath::vec1((filter_policy == FilterPolicy::ThreePoint) as i32),
                         );
+                    }
 
-                        if let Some(albedo_index) = material.albedo {
-                            let texture = &self.assets.textures2d[albedo_index];
-                            program.load_uniform_texture2d(
-                                texture,
-                                crate::shader::fragment::ALBEDO_TEX,
-                            );
-                            program.load_uniform_vec(
-                                "albedo",
-                                cgmath::vec1(crate::shader::fragment::ALBEDO_TEX as i32),
-                            );
-                        } else {
-                            program.load_uniform_vec("color", material.base_color.unwrap());
-                        }
-
-                        program.as_context(|| {
-                            primitive.render();
-                        });
+                    for (name, parameter) in material.parameters.iter() {
+                        parameter.load(program, name);
                     }
                 }
-                unsafe {
-                    gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, 0);
-                    gl::BlitFramebuffer(
-                        0,
-                        0,
-                        dimensions.0 as i32,
-                        dimensions.1 as i32,
-                        self.screen.0,
-                        self.screen.1,
-                        self.screen.2,
-                        self.screen.3,
-                        gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT,
-                        gl::NEAREST,
-                    );
+
+                match item.instances {
+                    Some((first, count)) => {
+                        let stride = std::mem::size_of::<InstanceData>();
+                        let offset = first * stride;
+                        self.instance_buffer.set_instance_matrix_ptr(
+                            crate::shader::vertex::INSTANCE_MODEL_LOCATION,
+                            (4, 4),
+                            stride,
+                            offset + std::mem::offset_of!(InstanceData, model),
+                        );
+                        self.instance_buffer.set_instance_matrix_ptr(
+                            crate::shader::vertex::INSTANCE_NORMAL_LOCATION,
+                            (3, 3),
+                            stride,
+                            offset + std::mem::offset_of!(InstanceData, normal_matrix),
+                        );
+                        primitive.draw_instanced(count);
+                    }
+                    None => primitive.draw(),
                 }
-            })
-            .unwrap();
+                stats.draw_calls += 1;
+            }
+            stats.state_changes = state.changes;
+            state.reset();
+
+            self.overlay.render(&mut self.assets.programs);
+
+            unsafe {
+                gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, 0);
+                gl::BlitFramebuffer(
+                    0,
+                    0,
+                    dimensions.0 as i32,
+                    dimensions.1 as i32,
+                    self.screen.0,
+                    self.screen.1,
+                    self.screen.2,
+                    self.screen.3,
+                    gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT,
+                    gl::NEAREST,
+                );
+            }
+        });
+
+        self.stats = stats;
 
         unsafe {
             let error = gl::GetError();
@@ -147,6 +592,277 @@ impl World {
         }
     }
 
+    /// Queues the visible primitives, instances of a group are merged into one item
+    /// with their transforms in the instance buffer
+    fn fill_queue(
+        &mut self,
+        frustum: &Frustum,
+        eye: cgmath::Vector3<f32>,
+        stats: &mut RenderStats,
+    ) {
+        self.queue.clear();
+        self.instances.clear();
+
+        let key = |node: &Node, program: usize, primitive: usize| {
+            let material = &self.assets.materials[node.materials[primitive]];
+            SortKey {
+                blended: material.alpha_mode == AlphaMode::Blend,
+                program,
+                texture: material.albedo.map(|albedo| albedo.texture),
+                mesh: node.mesh,
+                material: node.materials[primitive],
+            }
+        };
+
+        let lod_selected = |node: &Node| {
+            node.lod
+                .is_none_or(|(group, level)| self.lod_groups[group].selected() == level)
+        };
+        let visible = |bounds: &Aabb| {
+            frustum.intersects(bounds)
+                && self
+                    .draw_distance
+                    .is_none_or(|distance| bounds.distance(eye) <= distance)
+        };
+
+        for (index, node) in self.nodes.iter().enumerate() {
+            if node.instance_group.is_some() || !lod_selected(node) {
+                continue;
+            }
+
+            for (i, bounds) in node.bounds.iter().enumerate() {
+                if !visible(bounds) {
+                    stats.culled += 1;
+                    continue;
+                }
+                stats.drawn += 1;
+
+                self.queue.push(DrawItem {
+                    key: key(node, node.programs[i], i),
+                    node: index,
+                    primitive: i,
+                    depth: (bounds.center() - eye).magnitude(),
+                    instances: None,
+                });
+            }
+        }
+
+        for group in self.instance_groups.iter() {
+            let first_node = &self.nodes[group.nodes[0]];
+
+            for (i, &program) in group.programs.iter().enumerate() {
+                let first = self.instances.len();
+                let mut selected = 0;
+                let mut depth = 0.0f32;
+                for &member in group.nodes.iter() {
+                    let node = &self.nodes[member];
+                    if !lod_selected(node) {
+                        continue;
+                    }
+                    selected += 1;
+
+                    if visible(&node.bounds[i]) {
+                        self.instances.push(InstanceData {
+                            model: node.transform,
+                            normal_matrix: normal_matrix(node.transform),
+                        });
+                        depth = depth.max((node.bounds[i].center() - eye).magnitude());
+                    }
+                }
+
+                let count = self.instances.len() - first;
+                stats.drawn += count;
+                stats.culled += selected - count;
+                if count > 0 {
+                    self.queue.push(DrawItem {
+                        key: key(first_node, program, i),
+                        node: group.nodes[0],
+                        primitive: i,
+                        depth,
+                        instances: Some((first, count)),
+                    });
+                }
+            }
+        }
+
+        self.queue.sort();
+        self.instance_buffer.fill_with(
+            bytemuck::cast_slice(self.instances.as_slice()),
+            DrawType::Stream,
+        );
+    }
+
+    pub fn render_stats(&self) -> RenderStats {
+        self.stats
+    }
+
+    /// Textures, programs and geometry built for the scene
+    pub fn load_stats(&self) -> LoadStats {
+        self.load_stats
+    }
+
+    /// View, projection and eye position of the active fixed shot, or of the camera
+    /// seen from `pose`
+    fn view_projection(
+        &self,
+        pose: Option<CameraPose>,
+    ) -> (
+        cgmath::Matrix4<f32>,
+        cgmath::Matrix4<f32>,
+        cgmath::Vector3<f32>,
+    ) {
+        let (view, projection, position) = match pose {
+            Some(pose) => {
+                let (view, projection) = self.camera.pose_view_projection(pose);
+                (view, projection, pose.position)
+            }
+            None => (
+                self.camera.view(),
+                self.camera.projection(),
+                self.camera.position(),
+            ),
+        };
+
+        match self.fixed_cameras.active_shot() {
+            Some(shot) if self.camera.mode() == CameraMode::Fixed => (
+                shot.view(position),
+                self.camera.projection_with_fovy(shot.fovy),
+                shot.position,
+            ),
+            _ => (view, projection, position),
+        }
+    }
+
+    pub fn filter_policy(&self) -> FilterPolicy {
+        self.filter_policy
+    }
+
+    /// Scene wide texture filtering, materials with their own policy keep it
+    pub fn set_filter_policy(&mut self, policy: FilterPolicy) {
+        self.filter_policy = policy;
+    }
+
+    /// Turn rate at full look stick deflection, radians per second
+    pub fn set_look_speed(&mut self, look_speed: f32) {
+        self.look_speed = look_speed;
+    }
+
+    /// 2D layer drawn over the scene
+    pub fn overlay(&self) -> &Overlay {
+        &self.overlay
+    }
+
+    pub fn overlay_mut(&mut self) -> &mut Overlay {
+        &mut self.overlay
+    }
+
+    /// Size of the low resolution framebuffer, the overlay's coordinate space
+    pub fn render_resolution(&self) -> (u32, u32) {
+        self.camera.dimensions
+    }
+
+    /// Last rendered frame at render resolution as RGBA8, top row first
+    pub fn screenshot(&self) -> Result<((u32, u32), Vec<u8>), FramebufferError> {
+        let framebuffer = &self.camera.framebuffer;
+        Ok((framebuffer.dimensions(), framebuffer.read_color(0)?))
+    }
+
+    pub fn camera(&self) -> &Camera {
+        &self.camera
+    }
+
+    /// Projection, clip planes and field of view are configured on the camera directly
+    pub fn camera_mut(&mut self) -> &mut Camera {
+        &mut self.camera
+    }
+
+    pub fn camera_mode(&self) -> CameraMode {
+        self.camera.mode()
+    }
+
+    pub fn set_camera_mode(&mut self, mode: CameraMode) {
+        if mode == CameraMode::Walk {
+            self.walker.reset();
+        }
+        self.camera.set_mode(mode);
+    }
+
+    pub fn camera_pose(&self) -> CameraPose {
+        self.camera.pose()
+    }
+
+    pub fn set_camera_pose(&mut self, pose: CameraPose) {
+        self.camera.set_pose(pose);
+    }
+
+    /// Records the camera pose every update until `stop_recording`
+    pub fn start_recording(&mut self) {
+        self.recorder = Some(Recorder::default());
+    }
+
+    pub fn stop_recording(&mut self) -> Option<CameraPath> {
+        self.recorder.take().map(Recorder::finish)
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recorder.is_some()
+    }
+
+    /// Drives the camera along the path, input is ignored until playback ends
+    pub fn play_camera_path(&mut self, path: CameraPath, looping: bool) {
+        self.playback = Some(Playback::new(path, looping));
+    }
+
+    pub fn stop_playback(&mut self) {
+        self.playback = None;
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playback.is_some()
+    }
+
+    /// Whether the scene has shots for `CameraMode::Fixed`
+    pub fn has_fixed_cameras(&self) -> bool {
+        !self.fixed_cameras.is_empty()
+    }
+
+    /// World space bounds of every node
+    pub fn scene_bounds(&self) -> Aabb {
+        (0..self.nodes.len())
+            .map(|node| self.node_bounds(node))
+            .fold(Aabb::empty(), Aabb::union)
+    }
+
+    fn node_bounds(&self, node: usize) -> Aabb {
+        let node = &self.nodes[node];
+        self.assets.meshes[node.mesh]
+            .bounds()
+            .transform(node.transform)
+    }
+
+    pub fn frame_scene(&mut self) {
+        let bounds = self.scene_bounds();
+        self.camera.frame(bounds);
+    }
+
+    /// Frames the nearest node in the center of the view, returns false if there is none
+    pub fn frame_selected(&mut self) -> bool {
+        let (origin, direction) = (self.camera.position(), self.camera.front());
+
+        let selected = (0..self.nodes.len())
+            .map(|node| self.node_bounds(node))
+            .filter_map(|bounds| Some((bounds.ray_distance(origin, direction)?, bounds)))
+            .min_by(|(a, _), (b, _)| a.total_cmp(b));
+
+        match selected {
+            Some((_, bounds)) => {
+                self.camera.frame(bounds);
+                true
+            }
+            None => false,
+        }
+    }
+
     pub fn update_viewport(&mut self, x: i32, y: i32, width: i32, height: i32) {
         self.screen.0 = x;
         self.screen.1 = y;
@@ -163,23 +879,221 @@ impl World {
             .set_dimensions((width.round() as u32, height.round() as u32));
     }
 
-    pub fn update(&mut self, input: &crate::InputState, delta: f32) {
+    pub fn is_shader_reloading(&self) -> bool {
+        self.shader_watcher.is_some()
+    }
+
+    /// Turns watching `shaders/` for changes on or off, e.g. for release builds
+    pub fn set_shader_reloading(&mut self, enabled: bool) {
+        if enabled != self.shader_watcher.is_some() {
+            self.shader_watcher = enabled.then(|| ShaderWatcher::new(SHADERS_PATH));
+        }
+    }
+
+    /// Relinks programs whose shader files changed on disk, none when nothing changed
+    /// or reloading is off. Polls the files at most every 250 ms.
+    pub fn reload_shaders(&mut self) -> Option<ShaderReload> {
+        let changed = self.shader_watcher.as_mut()?.poll();
+        if changed.is_empty() {
+            return None;
+        }
+
+        let mut reload = ShaderReload {
+            changed,
+            ..ShaderReload::default()
+        };
+        for program in self.assets.programs.iter_mut() {
+            if !reload.changed.iter().any(|path| program.depends_on(path)) {
+                continue;
+            }
+
+            match program.reload() {
+                Ok(()) => reload.relinked += 1,
+                Err(log) => reload.errors.push(log),
+            }
+        }
+
+        Some(reload)
+    }
+
+    pub fn update(&mut self, input: &InputState, delta: f32) {
+        self.previous_pose = Some(self.camera.pose());
+
         const SHIFT_MULTIPLIER: f32 = 3.5;
 
-        let shift = if input.shift { SHIFT_MULTIPLIER } else { 1.0 };
-        let front = if input.w { 1.0 } else { 0.0 } * delta * shift;
-        let right = if input.d { 1.0 } else { 0.0 } * delta * shift;
-        let back = if input.s { 1.0 } else { 0.0 } * delta * shift;
-        let left = if input.a { 1.0 } else { 0.0 } * delta * shift;
-        let up = if input.e { 1.0 } else { 0.0 } * delta;
-        let down = if input.q { 1.0 } else { 0.0 } * delta;
+        let sprint = if input.is_held(Action::Sprint) {
+            SHIFT_MULTIPLIER
+        } else {
+            1.0
+        };
+        let front = input.amount(Action::MoveForward) * delta * sprint;
+        let right = input.amount(Action::MoveRight) * delta * sprint;
+        let back = input.amount(Action::MoveBack) * delta * sprint;
+        let left = input.amount(Action::MoveLeft) * delta * sprint;
+        let up = input.amount(Action::MoveUp) * delta;
+        let down = input.amount(Action::MoveDown) * delta;
 
+        // Sticks turn at a fixed rate, mouse input is relative to the window size
+        let stick_look = self.look_speed * delta / MOUSE_SENSIVITY;
         let mouse = (
-            input.mouse_rel.0 as f32 / self.screen.2 as f32 * delta,
-            input.mouse_rel.1 as f32 / self.screen.3 as f32 * delta,
+            input.mouse_rel.0 as f32 / self.screen.2 as f32 * delta
+                + input.look_axis.0 * stick_look,
+            input.mouse_rel.1 as f32 / self.screen.3 as f32 * delta
+                + input.look_axis.1 * stick_look,
         );
 
-        self.camera
-            .update(front, right, back, left, up, down, mouse);
+        match self
+            .playback
+            .as_mut()
+            .map(|playback| playback.advance(delta))
+        {
+            Some(Some(pose)) => self.camera.set_pose(pose),
+            Some(None) => self.playback = None,
+            None => {
+                self.camera
+                    .update(front, right, back, left, up, down, mouse);
+                self.camera.zoom(input.wheel as f32);
+
+                if self.camera.mode() == CameraMode::Walk {
+                    let forward = self.camera.front();
+                    let forward = cgmath::vec3(forward.x, 0.0, forward.z).normalize();
+                    let right_direction = forward.cross(WORLD_UP);
+                    let displacement =
+                        WALK_SPEED * ((front - back) * forward + (right - left) * right_direction);
+
+                    let eye = self.walker.update(
+                        &self.collision,
+                        self.camera.position(),
+                        displacement,
+                        input.is_held(Action::Jump),
+                        delta,
+                    );
+                    self.camera.set_position(eye);
+                }
+            }
+        }
+
+        if let Some(recorder) = self.recorder.as_mut() {
+            recorder.update(self.camera.pose(), delta);
+        }
+
+        if self.camera.mode() == CameraMode::Fixed {
+            self.fixed_cameras.update(self.camera.position());
+        }
     }
 }
+
+/// Program permutation of the material for the primitive, the log if it doesn't compile
+fn build_program(
+    storage: &mut AssetStorage,
+    settings: &SceneSettings,
+    material: usize,
+    vertex_colored: bool,
+    instanced: bool,
+) -> Result<usize, String> {
+    let material = &storage.materials[material];
+    let mut features = material.features(settings);
+    features.vertex_colored = vertex_colored;
+    features.instanced = instanced;
+
+    storage.techniques.program(
+        material.technique,
+        features,
+        &mut storage.programs,
+        |program| {
+            program.bind_uniform_block(
+                crate::shader::block::FRAME_NAME,
+                crate::shader::block::FRAME_BINDING,
+            )
+        },
+    )
+}
+
+/// Groups nodes with the same mesh, materials and winding order whose techniques support instancing
+fn group_instances(
+    storage: &mut AssetStorage,
+    settings: &SceneSettings,
+    nodes: &mut [Node],
+) -> Result<Vec<InstanceGroup>, String> {
+    let mut candidates: HashMap<(usize, &[usize], bool), Vec<usize>> = HashMap::new();
+    for (index, node) in nodes.iter().enumerate() {
+        let instancing = node.materials.iter().all(|&material| {
+            let technique = storage.materials[material].technique;
+            storage.techniques.get(technique).supports_instancing()
+        });
+
+        if instancing {
+            let mirrored = node.transform.determinant() < 0.0;
+            candidates
+                .entry((node.mesh, &node.materials, mirrored))
+                .or_default()
+                .push(index);
+        }
+    }
+
+    let mut candidates: Vec<_> = candidates
+        .into_iter()
+        .filter(|(_, members)| members.len() >= MIN_INSTANCES)
+        .map(|((mesh, materials, _), members)| (mesh, materials.to_vec(), members))
+        .collect();
+    // Deterministic group order
+    candidates.sort_by_key(|(_, _, members)| members[0]);
+
+    let mut groups = Vec::with_capacity(candidates.len());
+    for (mesh, materials, members) in candidates {
+        let colored: Vec<_> = storage.meshes[mesh]
+            .0
+            .iter()
+            .map(Primitive::has_colors)
+            .collect();
+        let programs = colored
+            .into_iter()
+            .zip(materials)
+            .map(|(colored, material)| build_program(storage, settings, material, colored, true))
+            .collect::<Result<_, _>>()?;
+
+        for &member in members.iter() {
+            nodes[member].instance_group = Some(groups.len());
+        }
+        groups.push(InstanceGroup {
+            nodes: members,
+            programs,
+        });
+    }
+
+    Ok(groups)
+}
+
+/// Inverse transpose of the upper 3x3, keeps normals perpendicular under non-uniform scale
+fn normal_matrix(transform: cgmath::Matrix4<f32>) -> cgmath::Matrix3<f32> {
+    let linear = cgmath::Matrix3::from_cols(
+        transform.x.truncate(),
+        transform.y.truncate(),
+        transform.z.truncate(),
+    );
+
+    linear
+        .invert()
+        .map(|inverse| inverse.transpose())
+        .unwrap_or(linear)
+}
+
+fn load_texture_ref(
+    program: &mut Program,
+    state: &mut GlState,
+    textures: &[Texture2D],
+    texture_ref: TextureRef,
+    name: &str,
+    tex_unit: u32,
+    filter_policy: FilterPolicy,
+) {
+    let texture = &textures[texture_ref.texture];
+    state.bind_texture(tex_unit, texture);
+    texture.apply_filter_policy(filter_policy);
+    program.load_uniform_vec(name, cgmath::vec1(tex_unit as i32));
+    program.load_uniform_vec(
+        &format!("{name}TexCoordSet"),
+        cgmath::vec1(texture_ref.tex_coord as i32),
+    );
+    program.load_uniform_mat(&format!("{name}Transform"), false, texture_ref.transform);
+}
@@ -0,0 +1,172 @@
+use crate::geometry::GeometryBuffer;
+use crate::shader::Program;
+use crate::texture::Texture2D;
+use crate::GlObject;
+
+/// Texture units tracked by `GlState`
+const TEXTURE_UNITS: usize = 4;
+
+/// Draw order of an opaque item, state changes are grouped by the most expensive state first.
+/// Blended items go last.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) struct SortKey {
+    pub blended: bool,
+    pub program: usize,
+    pub texture: Option<usize>,
+    pub mesh: usize,
+    pub material: usize,
+}
+
+/// A primitive of a node to draw
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct DrawItem {
+    pub key: SortKey,
+    /// The first node of instanced draws
+    pub node: usize,
+    pub primitive: usize,
+    /// Distance from the eye to the center of the bounds, the farthest instance for instanced draws
+    pub depth: f32,
+    /// First instance transform in the frame's instance buffer and the instance count
+    pub instances: Option<(usize, usize)>,
+}
+
+/// Draw items of a frame, the allocation is reused between frames
+#[derive(Debug, Default)]
+pub(crate) struct RenderQueue {
+    items: Vec<DrawItem>,
+}
+
+impl RenderQueue {
+    pub fn clear(&mut self) {
+        self.items.clear();
+    }
+
+    pub fn push(&mut self, item: DrawItem) {
+        self.items.push(item);
+    }
+
+    /// Opaque items by state, then blended items back to front
+    pub fn sort(&mut self) {
+        self.items
+            .sort_by(|a, b| match (a.key.blended, b.key.blended) {
+                (true, true) => b.depth.total_cmp(&a.depth),
+                _ => a.key.cmp(&b.key),
+            });
+    }
+
+    pub fn items(&self) -> &[DrawItem] {
+        &self.items
+    }
+}
+
+/// GL state set while drawing a queue, calls are only issued when the state differs.
+/// Starts unknown, so the first use of each state is always set.
+#[derive(Debug, Default)]
+pub(crate) struct GlState {
+    program: Option<usize>,
+    /// Material whose uniforms are loaded into the bound program
+    material: Option<usize>,
+    geometry_bound: bool,
+    active_unit: Option<u32>,
+    textures: [Option<u32>; TEXTURE_UNITS],
+    cull_face: Option<bool>,
+    clockwise: Option<bool>,
+    blend: Option<bool>,
+    /// Issued state changes
+    pub changes: usize,
+}
+
+impl GlState {
+    /// Binds the program, returns whether it changed
+    pub fn use_program(&mut self, index: usize, program: &Program) -> bool {
+        if self.program == Some(index) {
+            return false;
+        }
+
+        program.bind();
+        self.program = Some(index);
+        self.material = None;
+        self.changes += 1;
+
+        true
+    }
+
+    /// Whether the material's uniforms have to be loaded into the bound program
+    pub fn use_material(&mut self, material: usize) -> bool {
+        let changed = self.material != Some(material);
+        self.material = Some(material);
+
+        changed
+    }
+
+    /// All primitives are drawn from one vertex array
+    pub fn bind_geometry(&mut self, geometry: &GeometryBuffer) {
+        if !self.geometry_bound {
+            geometry.bind();
+            self.geometry_bound = true;
+            self.changes += 1;
+        }
+    }
+
+    pub fn bind_texture(&mut self, unit: u32, texture: &Texture2D) {
+        if self.active_unit != Some(unit) {
+            unsafe { gl::ActiveTexture(gl::TEXTURE0 + unit) };
+            self.active_unit = Some(unit);
+        }
+
+        let bound = &mut self.textures[unit as usize];
+        if *bound != Some(texture.glid()) {
+            unsafe { gl::BindTexture(gl::TEXTURE_2D, texture.glid()) };
+            *bound = Some(texture.glid());
+            self.changes += 1;
+        }
+    }
+
+    pub fn set_cull_face(&mut self, enabled: bool) {
+        if self.cull_face != Some(enabled) {
+            unsafe { toggle(gl::CULL_FACE, enabled) };
+            self.cull_face = Some(enabled);
+            self.changes += 1;
+        }
+    }
+
+    /// Mirroring transforms flip the winding order
+    pub fn set_clockwise(&mut self, clockwise: bool) {
+        if self.clockwise != Some(clockwise) {
+            unsafe { gl::FrontFace(if clockwise { gl::CW } else { gl::CCW }) };
+            self.clockwise = Some(clockwise);
+            self.changes += 1;
+        }
+    }
+
+    pub fn set_blend(&mut self, enabled: bool) {
+        if self.blend != Some(enabled) {
+            unsafe {
+                toggle(gl::BLEND, enabled);
+                if enabled {
+                    gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+                }
+            }
+            self.blend = Some(enabled);
+            self.changes += 1;
+        }
+    }
+
+    /// Leaves the defaults the rest of the renderer expects
+    pub fn reset(self) {
+        unsafe {
+            gl::BindVertexArray(0);
+            gl::Disable(gl::CULL_FACE);
+            gl::Disable(gl::BLEND);
+            gl::FrontFace(gl::CCW);
+        }
+    }
+}
+
+unsafe fn toggle(capability: gl::types::GLenum, enabled: bool) {
+    if enabled {
+        gl::Enable(capability);
+    } else {
+        gl::Disable(capability);
+    }
+}
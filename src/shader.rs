@@ -1,18 +1,36 @@
+mod preprocessor;
 mod program;
 mod shader;
+mod variant;
+mod watcher;
 
 pub mod uniform;
 
-pub use program::Program;
+pub use preprocessor::{preprocess, Preprocessed};
+pub use program::{MissingUniform, Program};
 pub use shader::Shader;
+pub use variant::{ProgramVariants, ShaderFeatures};
+pub use watcher::ShaderWatcher;
 
 pub mod vertex {
     pub(crate) const POSITION_LOCATION: u32 = 0;
     pub(crate) const NORMAL_LOCATION: u32 = 1;
     pub(crate) const TEXTURE_LOCATION: u32 = 2;
+    pub(crate) const TEXTURE_1_LOCATION: u32 = 3;
+    pub(crate) const COLOR_LOCATION: u32 = 4;
+    /// Model matrix of instanced draws, one column per location
+    pub(crate) const INSTANCE_MODEL_LOCATION: u32 = 5;
+    /// Normal matrix of instanced draws, after the model matrix columns
+    pub(crate) const INSTANCE_NORMAL_LOCATION: u32 = 9;
 }
 
 pub mod fragment {
     pub(crate) const ALBEDO_TEX: u32 = 0;
-    pub(crate) const NORMAL_MAP_TEX: u32 = 1;
+    pub(crate) const EMISSIVE_TEX: u32 = 2;
+    pub(crate) const SKY_TEX: u32 = 0;
+}
+
+pub mod block {
+    pub(crate) const FRAME_NAME: &str = "Frame";
+    pub(crate) const FRAME_BINDING: u32 = 0;
 }
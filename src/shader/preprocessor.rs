@@ -0,0 +1,107 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+/// GLSL source with includes resolved
+#[derive(Debug, Default)]
+pub struct Preprocessed {
+    pub code: String,
+    /// Every file the code was assembled from, indexed by the source string
+    /// number used in `#line` directives
+    pub files: Vec<PathBuf>,
+}
+
+/// Resolves `#include "path"` directives relative to the including file and
+/// injects `#define`s right after `#version`. Every file is included once.
+pub fn preprocess<P: AsRef<Path>>(path: P, defines: &[String]) -> Result<Preprocessed, String> {
+    let mut output = Preprocessed::default();
+    let mut included = HashSet::new();
+
+    include_file(path.as_ref(), defines, &mut output, &mut included)?;
+
+    Ok(output)
+}
+
+fn include_file(
+    path: &Path,
+    defines: &[String],
+    output: &mut Preprocessed,
+    included: &mut HashSet<PathBuf>,
+) -> Result<(), String> {
+    let canonical = path
+        .canonicalize()
+        .map_err(|error| format!("{}: {error}", path.display()))?;
+    if !included.insert(canonical.clone()) {
+        return Ok(());
+    }
+
+    let source = std::fs::read_to_string(&canonical)
+        .map_err(|error| format!("{}: {error}", path.display()))?;
+
+    let index = output.files.len();
+    output.files.push(display_path(&canonical));
+
+    let is_root = index == 0;
+    let mut defines_injected = !is_root;
+    if !is_root {
+        output.code.push_str(&format!("#line 1 {index}\n"));
+    }
+
+    for (i, line) in source.lines().enumerate() {
+        // Lines are numbered from 1, so the next one is i + 2
+        let next_line = i + 2;
+        let directive = line.trim_start();
+
+        if let Some(include) = directive.strip_prefix("#include") {
+            let name = include.trim();
+            let name = name
+                .strip_prefix('"')
+                .and_then(|name| name.strip_suffix('"'))
+                .ok_or_else(|| {
+                    format!("{}:{}: expected #include \"file\"", path.display(), i + 1)
+                })?;
+
+            let parent = canonical.parent().unwrap_or(Path::new("."));
+            include_file(&parent.join(name), &[], output, included)?;
+            output
+                .code
+                .push_str(&format!("#line {next_line} {index}\n"));
+            continue;
+        }
+
+        // `#version` has to stay the first directive, only comments may precede it
+        let preamble = directive.is_empty() || directive.starts_with("//");
+        if !defines_injected && !preamble && !directive.starts_with("#version") {
+            inject_defines(defines, i + 1, index, &mut output.code);
+            defines_injected = true;
+        }
+
+        output.code.push_str(line);
+        output.code.push('\n');
+
+        if !defines_injected && directive.starts_with("#version") {
+            inject_defines(defines, next_line, index, &mut output.code);
+            defines_injected = true;
+        }
+    }
+
+    Ok(())
+}
+
+fn inject_defines(defines: &[String], next_line: usize, index: usize, code: &mut String) {
+    if defines.is_empty() {
+        return;
+    }
+
+    for define in defines {
+        code.push_str(&format!("#define {define}\n"));
+    }
+    code.push_str(&format!("#line {next_line} {index}\n"));
+}
+
+/// Paths inside the working directory are shown relative to it
+fn display_path(path: &Path) -> PathBuf {
+    std::env::current_dir()
+        .ok()
+        .and_then(|dir| path.strip_prefix(dir).ok().map(Path::to_path_buf))
+        .unwrap_or_else(|| path.to_path_buf())
+}
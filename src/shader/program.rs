@@ -1,31 +1,73 @@
 use super::shader::Shader;
 use super::uniform::*;
 
+use crate::leak::{self, GlKind};
 use crate::texture::Texture2D;
 use crate::GlObject;
 
-use std::ffi::CString;
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+thread_local! {
+    static BOUND_PROGRAM: Cell<u32> = const { Cell::new(0) };
+}
+
+/// What happens when a uniform is not active in the program, e.g. optimized out by the driver
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum MissingUniform {
+    Ignore,
+    /// Prints a warning once per uniform name
+    #[default]
+    Warn,
+    Panic,
+}
 
 #[derive(Debug)]
-pub struct Program(u32);
+pub struct Program {
+    id: u32,
+    /// Active uniform locations queried once after linking
+    uniforms: HashMap<String, i32>,
+    uniform_blocks: HashMap<String, u32>,
+    block_bindings: HashMap<String, u32>,
+    missing_policy: MissingUniform,
+    reported_missing: RefCell<HashSet<String>>,
+
+    /// Shader files and defines the program was built from, used for reloading
+    sources: Vec<(PathBuf, gl::types::GLenum)>,
+    defines: Vec<String>,
+    /// Canonical paths of sources and everything they include
+    dependencies: Vec<PathBuf>,
+}
 
 impl Program {
     pub fn from_shaders(shaders: impl IntoIterator<Item = Shader>) -> Result<Self, String> {
         unsafe {
-            let program = match gl::CreateProgram() {
+            let mut program = match gl::CreateProgram() {
                 0 => return Err("Can't allocate program descriptor".to_string()),
-                program => Program(program),
+                program => Program {
+                    id: program,
+                    uniforms: HashMap::new(),
+                    uniform_blocks: HashMap::new(),
+                    block_bindings: HashMap::new(),
+                    missing_policy: MissingUniform::default(),
+                    reported_missing: RefCell::new(HashSet::new()),
+                    sources: Vec::new(),
+                    defines: Vec::new(),
+                    dependencies: Vec::new(),
+                },
             };
+            leak::created(GlKind::Program);
 
             for shader in shaders {
-                gl::AttachShader(program.0, shader.0);
+                gl::AttachShader(program.id, shader.0);
             }
 
-            gl::LinkProgram(program.0);
+            gl::LinkProgram(program.id);
 
             let success = {
                 let mut success = 0;
-                gl::GetProgramiv(program.0, gl::LINK_STATUS, &mut success);
+                gl::GetProgramiv(program.id, gl::LINK_STATUS, &mut success);
 
                 success != 0
             };
@@ -35,7 +77,7 @@ impl Program {
                 let mut buf: Vec<u8> = Vec::with_capacity(BUF_SIZE);
                 let mut log_len = 0;
                 gl::GetProgramInfoLog(
-                    program.0,
+                    program.id,
                     BUF_SIZE as i32,
                     &mut log_len,
                     buf.as_mut_ptr().cast(),
@@ -44,40 +86,185 @@ impl Program {
                 buf.set_len(log_len.try_into().unwrap());
                 Err(String::from_utf8_lossy(&buf).to_string())
             } else {
+                program.reflect();
                 Ok(program)
             }
         }
     }
 
+    pub fn from_files<P: AsRef<Path>>(
+        sources: impl IntoIterator<Item = (P, gl::types::GLenum)>,
+    ) -> Result<Self, String> {
+        Self::from_files_with_defines(sources, Vec::new())
+    }
+
+    /// Builds the program from preprocessed files, `defines` are injected into every shader
+    pub fn from_files_with_defines<P: AsRef<Path>>(
+        sources: impl IntoIterator<Item = (P, gl::types::GLenum)>,
+        defines: Vec<String>,
+    ) -> Result<Self, String> {
+        let sources: Vec<_> = sources
+            .into_iter()
+            .map(|(path, shader_type)| (path.as_ref().to_path_buf(), shader_type))
+            .collect();
+
+        let mut shaders = Vec::with_capacity(sources.len());
+        let mut dependencies = Vec::new();
+        for (path, shader_type) in sources.iter() {
+            let (shader, files) = Shader::from_file_with_defines(path, *shader_type, &defines)?;
+
+            shaders.push(shader);
+            dependencies.extend(files.iter().filter_map(|file| file.canonicalize().ok()));
+        }
+
+        let mut program = Self::from_shaders(shaders).map_err(|log| {
+            let files: Vec<_> = sources.iter().map(|(path, _)| path.display()).collect();
+            format!("Linking {files:?} with {defines:?} failed:\n{log}")
+        })?;
+        program.sources = sources;
+        program.defines = defines;
+        program.dependencies = dependencies;
+
+        Ok(program)
+    }
+
+    /// Rebuilds the program from its files, keeps the current one if it fails
+    pub fn reload(&mut self) -> Result<(), String> {
+        let mut program =
+            Self::from_files_with_defines(self.sources.clone(), self.defines.clone())?;
+        program.missing_policy = self.missing_policy;
+        for (name, binding) in self.block_bindings.iter() {
+            program.bind_uniform_block(name, *binding);
+        }
+
+        *self = program;
+        Ok(())
+    }
+
+    /// Whether the program was built from `path` or includes it, expects a canonical path
+    pub fn depends_on(&self, path: &Path) -> bool {
+        self.dependencies
+            .iter()
+            .any(|dependency| dependency == path)
+    }
+
+    fn reflect(&mut self) {
+        const BUF_SIZE: usize = 256;
+
+        unsafe {
+            let mut count = 0;
+            gl::GetProgramiv(self.id, gl::ACTIVE_UNIFORMS, &mut count);
+
+            for index in 0..count as u32 {
+                let mut buf = [0u8; BUF_SIZE];
+                let (mut len, mut size, mut uniform_type) = (0, 0, 0);
+                gl::GetActiveUniform(
+                    self.id,
+                    index,
+                    BUF_SIZE as i32,
+                    &mut len,
+                    &mut size,
+                    &mut uniform_type,
+                    buf.as_mut_ptr().cast(),
+                );
+
+                let name = String::from_utf8_lossy(&buf[..len as usize]).to_string();
+                let location = gl::GetUniformLocation(self.id, buf.as_ptr().cast());
+
+                // Members of uniform blocks have no location
+                if location != -1 {
+                    // Arrays are reported as `name[0]`
+                    let name = name.strip_suffix("[0]").unwrap_or(&name).to_string();
+                    self.uniforms.insert(name, location);
+                }
+            }
+
+            let mut count = 0;
+            gl::GetProgramiv(self.id, gl::ACTIVE_UNIFORM_BLOCKS, &mut count);
+
+            for index in 0..count as u32 {
+                let mut buf = [0u8; BUF_SIZE];
+                let mut len = 0;
+                gl::GetActiveUniformBlockName(
+                    self.id,
+                    index,
+                    BUF_SIZE as i32,
+                    &mut len,
+                    buf.as_mut_ptr().cast(),
+                );
+
+                let name = String::from_utf8_lossy(&buf[..len as usize]).to_string();
+                self.uniform_blocks.insert(name, index);
+            }
+        }
+    }
+
+    pub fn set_missing_uniform_policy(&mut self, policy: MissingUniform) {
+        self.missing_policy = policy;
+    }
+
+    pub fn has_uniform(&self, name: &str) -> bool {
+        self.uniforms.contains_key(name)
+    }
+
+    fn location(&self, name: &str) -> Option<i32> {
+        let location = self.uniforms.get(name).copied();
+
+        if location.is_none() {
+            match self.missing_policy {
+                MissingUniform::Ignore => {}
+                MissingUniform::Warn => {
+                    if self.reported_missing.borrow_mut().insert(name.to_string()) {
+                        eprintln!("Program {}: uniform \"{name}\" is not active", self.id);
+                    }
+                }
+                MissingUniform::Panic => {
+                    panic!("Program {}: uniform \"{name}\" is not active", self.id)
+                }
+            }
+        }
+
+        location
+    }
+
+    /// Binds the program unless it is already bound
+    pub(crate) fn bind(&self) {
+        BOUND_PROGRAM.with(|bound| {
+            if bound.get() != self.id {
+                unsafe {
+                    gl::UseProgram(self.id);
+                }
+                bound.set(self.id);
+            }
+        });
+    }
+
     pub fn as_context<F, R>(&self, mut closure: F) -> R
     where
         F: FnMut() -> R,
     {
-        unsafe {
-            gl::UseProgram(self.0);
-        }
+        self.bind();
 
         let result = closure();
 
         #[cfg(debug_assertions)]
         unsafe {
             gl::UseProgram(0);
+            BOUND_PROGRAM.with(|bound| bound.set(0));
         }
 
         result
     }
 
     pub fn load_uniform_vec<T, const N: usize, V: UniformVec<T, N>>(&mut self, name: &str, vec: V) {
-        let name = CString::new(name).unwrap();
-
-        self.as_context(|| unsafe {
-            let location = gl::GetUniformLocation(self.0, name.as_ptr());
+        if let Some(location) = self.location(name) {
+            self.bind();
 
-            assert_ne!(location, -1);
-
-            let data: &[T; N] = vec.as_ref();
-            V::LOADER(location, 1, data.as_ptr());
-        });
+            unsafe {
+                let data: &[T; N] = vec.as_ref();
+                V::LOADER(location, 1, data.as_ptr());
+            }
+        }
     }
 
     pub fn load_uniform_mat<T, const N: usize, M: UniformMat<T, N>>(
@@ -86,33 +273,58 @@ impl Program {
         transpose: bool,
         mat: M,
     ) {
-        let name = CString::new(name).unwrap();
-
-        self.as_context(|| unsafe {
-            let location = gl::GetUniformLocation(self.0, name.as_ptr());
+        if let Some(location) = self.location(name) {
+            self.bind();
 
-            assert_ne!(location, -1);
-
-            let data: &[T; N] = mat.as_ref();
-            M::LOADER(
-                location,
-                1,
-                if transpose { gl::TRUE } else { gl::FALSE },
-                data.as_ptr(),
-            );
-        });
+            unsafe {
+                let data: &[T; N] = mat.as_ref();
+                M::LOADER(
+                    location,
+                    1,
+                    if transpose { gl::TRUE } else { gl::FALSE },
+                    data.as_ptr(),
+                );
+            }
+        }
     }
 
     pub fn load_uniform_texture2d(&mut self, texture: &Texture2D, tex_unit: u32) {
-        self.as_context(|| unsafe {
+        unsafe {
             gl::ActiveTexture(gl::TEXTURE0 + tex_unit);
             gl::BindTexture(gl::TEXTURE_2D, texture.glid());
-        });
+        }
+    }
+
+    /// Connects the uniform block to the buffer bound at `binding`
+    pub fn bind_uniform_block(&mut self, name: &str, binding: u32) {
+        self.block_bindings.insert(name.to_string(), binding);
+
+        match self.uniform_blocks.get(name) {
+            Some(&index) => unsafe { gl::UniformBlockBinding(self.id, index, binding) },
+            // Drivers drop blocks no active code reads, so this is never fatal
+            None => {
+                let report = self.missing_policy != MissingUniform::Ignore
+                    && self.reported_missing.borrow_mut().insert(name.to_string());
+                if report {
+                    eprintln!(
+                        "Program {}: uniform block \"{name}\" is not active",
+                        self.id
+                    );
+                }
+            }
+        }
     }
 }
 
 impl Drop for Program {
     fn drop(&mut self) {
-        unsafe { gl::DeleteProgram(self.0) }
+        BOUND_PROGRAM.with(|bound| {
+            if bound.get() == self.id {
+                bound.set(0);
+            }
+        });
+
+        unsafe { gl::DeleteProgram(self.id) }
+        leak::deleted(GlKind::Program);
     }
 }
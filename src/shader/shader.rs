@@ -1,4 +1,7 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
+
+use super::preprocessor::preprocess;
+use crate::leak::{self, GlKind};
 
 #[derive(Debug)]
 pub struct Shader(pub(super) u32);
@@ -8,8 +11,21 @@ impl Shader {
         path: P,
         shader_type: gl::types::GLenum,
     ) -> Result<Shader, String> {
-        let source = std::fs::read_to_string(path).unwrap();
-        Shader::from_source(&source, shader_type)
+        Self::from_file_with_defines(path, shader_type, &[]).map(|(shader, _)| shader)
+    }
+
+    /// Preprocesses the file, returns the shader together with every file it was assembled from
+    pub fn from_file_with_defines<P: AsRef<Path>>(
+        path: P,
+        shader_type: gl::types::GLenum,
+        defines: &[String],
+    ) -> Result<(Shader, Vec<PathBuf>), String> {
+        let source = preprocess(path, defines)?;
+
+        let shader = Shader::from_source(&source.code, shader_type)
+            .map_err(|log| map_log_locations(&log, &source.files))?;
+
+        Ok((shader, source.files))
     }
 
     pub fn from_source(source: &str, shader_type: gl::types::GLenum) -> Result<Shader, String> {
@@ -18,6 +34,7 @@ impl Shader {
                 0 => return Err("Can't allocate shader descriptor".to_string()),
                 shader => Shader(shader),
             };
+            leak::created(GlKind::Shader);
 
             gl::ShaderSource(
                 shader.0,
@@ -58,5 +75,56 @@ impl Shader {
 impl Drop for Shader {
     fn drop(&mut self) {
         unsafe { gl::DeleteShader(self.0) }
+        leak::deleted(GlKind::Shader);
     }
 }
+
+/// Replaces `source:line` locations in a driver info log with `file:line`,
+/// `files` are indexed by GLSL source string number
+pub(crate) fn map_log_locations(log: &str, files: &[PathBuf]) -> String {
+    let mut mapped = String::with_capacity(log.len());
+
+    for line in log.lines() {
+        match parse_location(line) {
+            Some((start, end, source, line_number)) if source < files.len() => {
+                mapped.push_str(&line[..start]);
+                mapped.push_str(&format!("{}:{line_number}", files[source].display()));
+                mapped.push_str(&line[end..]);
+            }
+            _ => mapped.push_str(line),
+        }
+        mapped.push('\n');
+    }
+
+    mapped
+}
+
+/// Finds the location prefix of a log line in one of the common driver formats:
+/// `0:12(5): error` (Mesa), `0(12) : error` (NVIDIA), `ERROR: 0:12: ...` (AMD, Intel).
+/// Returns byte range of the location, source string number and line number.
+fn parse_location(line: &str) -> Option<(usize, usize, usize, usize)> {
+    let start = ["ERROR: ", "WARNING: "]
+        .iter()
+        .find_map(|prefix| line.starts_with(prefix).then_some(prefix.len()))
+        .unwrap_or(0);
+    let rest = &line[start..];
+
+    let source_len = rest.find(|c: char| !c.is_ascii_digit())?;
+    let source = rest[..source_len].parse().ok()?;
+
+    let separator = rest[source_len..].chars().next()?;
+    if separator != ':' && separator != '(' {
+        return None;
+    }
+
+    let number = &rest[source_len + 1..];
+    let number_len = number.find(|c: char| !c.is_ascii_digit())?;
+    let line_number = number[..number_len].parse().ok()?;
+
+    let mut end = start + source_len + 1 + number_len;
+    if separator == '(' && line[end..].starts_with(')') {
+        end += 1;
+    }
+
+    Some((start, end, source, line_number))
+}
@@ -0,0 +1,97 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use super::Program;
+
+/// Material and geometry features a program permutation is compiled for
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ShaderFeatures {
+    pub textured: bool,
+    pub emissive_mapped: bool,
+    pub vertex_colored: bool,
+    /// Per-vertex (Gouraud) lighting
+    pub lit: bool,
+    pub alpha_tested: bool,
+    /// Affine texture mapping like on the PSX GPU
+    pub affine: bool,
+    /// PSX ordered dithering before 15 bit color quantization
+    pub dithered: bool,
+    /// Model matrix from a per-instance attribute
+    pub instanced: bool,
+    /// Per-vertex depth cueing towards the fog color
+    pub fogged: bool,
+}
+
+impl ShaderFeatures {
+    pub fn defines(&self) -> Vec<String> {
+        [
+            (self.textured, "TEXTURED"),
+            (self.emissive_mapped, "EMISSIVE_MAP"),
+            (self.vertex_colored, "VERTEX_COLORED"),
+            (self.lit, "LIT"),
+            (self.alpha_tested, "ALPHA_TEST"),
+            (self.affine, "AFFINE"),
+            (self.dithered, "DITHERED"),
+            (self.instanced, "INSTANCED"),
+            (self.fogged, "FOG"),
+        ]
+        .into_iter()
+        .filter(|(enabled, _)| *enabled)
+        .map(|(_, define)| define.to_string())
+        .collect()
+    }
+}
+
+/// Builds program permutations of a vertex/fragment shader pair on demand
+#[derive(Debug)]
+pub struct ProgramVariants {
+    vertex: PathBuf,
+    fragment: PathBuf,
+    /// Index of the built permutation in the program storage
+    programs: HashMap<ShaderFeatures, usize>,
+}
+
+impl ProgramVariants {
+    pub fn new<P: Into<PathBuf>>(vertex: P, fragment: P) -> Self {
+        Self {
+            vertex: vertex.into(),
+            fragment: fragment.into(),
+            programs: HashMap::new(),
+        }
+    }
+
+    /// Returns index of the permutation in `programs`, building it if needed
+    pub fn get_or_build(
+        &mut self,
+        features: ShaderFeatures,
+        programs: &mut Vec<Program>,
+        setup: impl FnOnce(&mut Program),
+    ) -> Result<usize, String> {
+        if let Some(&index) = self.programs.get(&features) {
+            return Ok(index);
+        }
+
+        let mut program = Program::from_files_with_defines(
+            [
+                (&self.vertex, gl::VERTEX_SHADER),
+                (&self.fragment, gl::FRAGMENT_SHADER),
+            ],
+            features.defines(),
+        )?;
+        setup(&mut program);
+
+        let index = programs.len();
+        programs.push(program);
+        self.programs.insert(features, index);
+
+        Ok(index)
+    }
+
+    pub fn len(&self) -> usize {
+        self.programs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.programs.is_empty()
+    }
+}
@@ -0,0 +1,69 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Polls modification times of every file under a directory
+#[derive(Debug)]
+pub struct ShaderWatcher {
+    root: PathBuf,
+    modified: HashMap<PathBuf, SystemTime>,
+    last_poll: Instant,
+}
+
+impl ShaderWatcher {
+    pub fn new<P: AsRef<Path>>(root: P) -> Self {
+        let mut watcher = Self {
+            root: root.as_ref().to_path_buf(),
+            modified: HashMap::new(),
+            last_poll: Instant::now(),
+        };
+        watcher.scan();
+
+        watcher
+    }
+
+    /// Returns canonical paths of files changed since the previous poll
+    pub fn poll(&mut self) -> Vec<PathBuf> {
+        if self.last_poll.elapsed() < POLL_INTERVAL {
+            return Vec::new();
+        }
+        self.last_poll = Instant::now();
+
+        self.scan()
+    }
+
+    fn scan(&mut self) -> Vec<PathBuf> {
+        let mut changed = Vec::new();
+        let mut directories = vec![self.root.clone()];
+
+        while let Some(directory) = directories.pop() {
+            let Ok(entries) = std::fs::read_dir(&directory) else {
+                continue;
+            };
+
+            for entry in entries.flatten() {
+                let path = entry.path();
+                let Ok(metadata) = entry.metadata() else {
+                    continue;
+                };
+
+                if metadata.is_dir() {
+                    directories.push(path);
+                    continue;
+                }
+
+                let (Ok(modified), Ok(path)) = (metadata.modified(), path.canonicalize()) else {
+                    continue;
+                };
+
+                if self.modified.insert(path.clone(), modified) != Some(modified) {
+                    changed.push(path);
+                }
+            }
+        }
+
+        changed
+    }
+}
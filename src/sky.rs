@@ -0,0 +1,237 @@
+use cgmath::Matrix;
+
+use crate::buffer::{Buffer, DrawType, Ebo, ObjectType, Vbo};
+use crate::shader::fragment::SKY_TEX;
+use crate::shader::vertex::POSITION_LOCATION;
+use crate::shader::Program;
+use crate::texture::{Texture2D, TextureCube};
+use crate::vao::Vao;
+use crate::GlObject;
+
+const VERTEX_PATH: &str = "shaders/sky/vert.glsl";
+const FRAGMENT_PATH: &str = "shaders/sky/frag.glsl";
+
+/// Background images of the scene
+#[derive(Debug)]
+pub(crate) enum SkyTexture {
+    Cubemap(TextureCube),
+    /// Equirectangular panorama, the center looks down -Z
+    Equirect(Texture2D),
+    /// PS1 styled flat image scrolling with the camera's heading and pitch
+    Backdrop {
+        texture: Texture2D,
+        /// Times the image is repeated around the horizon
+        repeat: f32,
+        /// Height of the horizon in the image, 0 at the top
+        horizon: f32,
+        /// Height divided by width, pixels keep their aspect when scrolled vertically
+        aspect: f32,
+    },
+}
+
+impl SkyTexture {
+    fn define(&self) -> &'static str {
+        match self {
+            SkyTexture::Cubemap(_) => "CUBEMAP",
+            SkyTexture::Equirect(_) => "EQUIRECT",
+            SkyTexture::Backdrop { .. } => "BACKDROP",
+        }
+    }
+}
+
+/// Draws the sky behind the scene: a cube around the camera for panoramas,
+/// a triangle covering the viewport for backdrops.
+/// With fog the horizon fades into the fog color, like the geometry at the draw distance.
+#[derive(Debug)]
+pub(crate) struct SkyRenderer {
+    /// Index in the program storage
+    program: usize,
+    vao: Vao,
+    _vbo: Vbo,
+    _ebo: Ebo,
+}
+
+impl SkyRenderer {
+    pub fn new(
+        texture: &SkyTexture,
+        fog: bool,
+        programs: &mut Vec<Program>,
+    ) -> Result<Self, String> {
+        let mut defines = vec![texture.define().to_string()];
+        if fog {
+            defines.push("FOG".to_string());
+        }
+        let mut program = Program::from_files_with_defines(
+            [
+                (VERTEX_PATH, gl::VERTEX_SHADER),
+                (FRAGMENT_PATH, gl::FRAGMENT_SHADER),
+            ],
+            defines,
+        )?;
+        // Backdrops don't transform vertices, only the fog reads the frame data then
+        if fog || !matches!(texture, SkyTexture::Backdrop { .. }) {
+            program.bind_uniform_block(
+                crate::shader::block::FRAME_NAME,
+                crate::shader::block::FRAME_BINDING,
+            );
+        }
+        programs.push(program);
+
+        let corners: Vec<f32> = (0..8)
+            .flat_map(|corner| {
+                [
+                    if corner & 1 == 0 { -1.0 } else { 1.0 },
+                    if corner & 2 == 0 { -1.0 } else { 1.0 },
+                    if corner & 4 == 0 { -1.0 } else { 1.0 },
+                ]
+            })
+            .collect();
+        // Two triangles per face, seen from inside so the winding doesn't matter
+        #[rustfmt::skip]
+        let indices: [u32; 36] = [
+            0, 1, 3, 0, 3, 2, // -Z
+            4, 5, 7, 4, 7, 6, // +Z
+            0, 2, 6, 0, 6, 4, // -X
+            1, 3, 7, 1, 7, 5, // +X
+            0, 1, 5, 0, 5, 4, // -Y
+            2, 3, 7, 2, 7, 6, // +Y
+        ];
+
+        let mut vao = Vao::new();
+        let mut vbo = Vbo::new();
+        let mut ebo = Ebo::new();
+        vao.as_context(|| {
+            vbo.fill_with(bytemuck::cast_slice(corners.as_slice()), DrawType::Static);
+            ebo.fill_with(bytemuck::cast_slice(indices.as_slice()), DrawType::Static);
+            vbo.set_interleaved_attrib_ptr(POSITION_LOCATION, 3, ObjectType::Float, false, 12, 0);
+        });
+
+        Ok(Self {
+            program: programs.len() - 1,
+            vao,
+            _vbo: vbo,
+            _ebo: ebo,
+        })
+    }
+
+    /// Draws without depth, before anything else of the frame.
+    /// Panoramas need a perspective projection and are skipped otherwise.
+    pub fn render(
+        &self,
+        texture: &SkyTexture,
+        programs: &mut [Program],
+        view: cgmath::Matrix4<f32>,
+        projection: cgmath::Matrix4<f32>,
+        (near, far): (f32, Option<f32>),
+    ) {
+        let perspective = projection.w.w == 0.0;
+        if !perspective && !matches!(texture, SkyTexture::Backdrop { .. }) {
+            return;
+        }
+
+        let program = &mut programs[self.program];
+        program.load_uniform_vec("sky", cgmath::vec1(SKY_TEX as i32));
+
+        // The sky is infinitely far away, only the view's rotation applies
+        let mut rotation = view;
+        rotation.w = cgmath::vec4(0.0, 0.0, 0.0, 1.0);
+        // Faces are 1 and corners √3 away from the center, the cube has to be between
+        // the clip planes
+        let scale = 2.0 * near;
+        debug_assert!(
+            far.is_none_or(|far| scale * 3f32.sqrt() < far),
+            "clip planes are too close for the sky"
+        );
+
+        let (target, id) = match texture {
+            SkyTexture::Cubemap(cube) => (gl::TEXTURE_CUBE_MAP, cube.glid()),
+            SkyTexture::Equirect(texture) | SkyTexture::Backdrop { texture, .. } => {
+                (gl::TEXTURE_2D, texture.glid())
+            }
+        };
+        match texture {
+            SkyTexture::Backdrop {
+                repeat,
+                horizon,
+                aspect,
+                ..
+            } => {
+                let angles = ViewAngles::new(view, projection);
+                program.load_uniform_vec(
+                    "backdropRect",
+                    angles.backdrop_rect(*repeat, *horizon, *aspect),
+                );
+                if program.has_uniform("backdropElevation") {
+                    program.load_uniform_vec("backdropElevation", angles.elevation());
+                }
+            }
+            _ => program.load_uniform_mat(
+                "skyViewProjection",
+                false,
+                projection * rotation * cgmath::Matrix4::from_scale(scale),
+            ),
+        }
+
+        program.bind();
+        self.vao.bind();
+        unsafe {
+            gl::Disable(gl::DEPTH_TEST);
+            gl::DepthMask(gl::FALSE);
+            gl::ActiveTexture(gl::TEXTURE0 + SKY_TEX);
+            gl::BindTexture(target, id);
+
+            match texture {
+                SkyTexture::Backdrop { .. } => gl::DrawArrays(gl::TRIANGLES, 0, 3),
+                _ => gl::DrawElements(gl::TRIANGLES, 36, gl::UNSIGNED_INT, std::ptr::null()),
+            }
+
+            gl::BindTexture(target, 0);
+            gl::BindVertexArray(0);
+            gl::DepthMask(gl::TRUE);
+            gl::Enable(gl::DEPTH_TEST);
+        }
+    }
+}
+
+/// Direction of the view and half of its field of view, in radians
+#[derive(Debug, Clone, Copy)]
+struct ViewAngles {
+    heading: f32,
+    pitch: f32,
+    half_width: f32,
+    half_height: f32,
+}
+
+impl ViewAngles {
+    fn new(view: cgmath::Matrix4<f32>, projection: cgmath::Matrix4<f32>) -> Self {
+        // The camera looks down -Z of the view space
+        let front = -view.row(2).truncate();
+
+        Self {
+            heading: front.x.atan2(-front.z),
+            pitch: front.y.clamp(-1.0, 1.0).asin(),
+            half_width: (1.0 / projection.x.x).atan(),
+            half_height: (1.0 / projection.y.y).atan(),
+        }
+    }
+
+    /// Texture coordinates of the bottom left and top right corners of the viewport.
+    /// Angles map linearly to the image like on a scrolling 2D background.
+    fn backdrop_rect(&self, repeat: f32, horizon: f32, aspect: f32) -> cgmath::Vector4<f32> {
+        // Angles covered by the image
+        let turn = std::f32::consts::TAU / repeat.max(f32::EPSILON);
+        let span = turn * aspect;
+
+        cgmath::vec4(
+            (self.heading - self.half_width) / turn,
+            horizon - (self.pitch - self.half_height) / span,
+            (self.heading + self.half_width) / turn,
+            horizon - (self.pitch + self.half_height) / span,
+        )
+    }
+
+    /// Angles above the horizon at the bottom and top of the viewport
+    fn elevation(&self) -> cgmath::Vector2<f32> {
+        cgmath::vec2(self.pitch - self.half_height, self.pitch + self.half_height)
+    }
+}
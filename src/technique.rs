@@ -0,0 +1,139 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::shader::{Program, ProgramVariants, ShaderFeatures};
+
+/// Lists the available techniques, shader paths in it are relative to the manifest
+pub const MANIFEST_PATH: &str = "shaders/techniques.json";
+/// Technique of materials that don't name one, always registered first
+pub const DEFAULT_TECHNIQUE: &str = "psx";
+
+/// Shading model, a vertex/fragment shader pair built in feature permutations
+#[derive(Debug)]
+pub struct Technique {
+    name: String,
+    variants: ProgramVariants,
+    /// The vertex shader reads the model matrix from an instance attribute with `INSTANCED`
+    instancing: bool,
+}
+
+impl Technique {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn supports_instancing(&self) -> bool {
+        self.instancing
+    }
+}
+
+/// Techniques by name, materials refer to them by index
+#[derive(Debug, Default)]
+pub struct TechniqueRegistry {
+    techniques: Vec<Technique>,
+    names: HashMap<String, usize>,
+}
+
+impl TechniqueRegistry {
+    /// Reads a manifest of the form
+    /// `{ "psx": { "vertex": "psx/vert.glsl", "fragment": "psx/frag.glsl", "instancing": true } }`
+    pub fn from_manifest<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+        let manifest = std::fs::read_to_string(path)
+            .map_err(|error| format!("{}: {error}", path.display()))?;
+        let manifest: serde_json::Value = serde_json::from_str(&manifest)
+            .map_err(|error| format!("{}: {error}", path.display()))?;
+        let entries = manifest
+            .as_object()
+            .ok_or_else(|| format!("{}: expected an object of techniques", path.display()))?;
+
+        if !entries.contains_key(DEFAULT_TECHNIQUE) {
+            return Err(format!(
+                "{}: default technique \"{DEFAULT_TECHNIQUE}\" is missing",
+                path.display()
+            ));
+        }
+
+        let root = path.parent().unwrap_or(Path::new("."));
+        let default = entries.get_key_value(DEFAULT_TECHNIQUE).into_iter();
+        let others = entries
+            .iter()
+            .filter(|(name, _)| name.as_str() != DEFAULT_TECHNIQUE);
+
+        let mut registry = Self::default();
+        for (name, entry) in default.chain(others) {
+            let shader = |stage: &str| {
+                entry
+                    .get(stage)
+                    .and_then(serde_json::Value::as_str)
+                    .map(|file| root.join(file))
+                    .ok_or_else(|| {
+                        format!(
+                            "{}: technique \"{name}\" has no {stage} shader",
+                            path.display()
+                        )
+                    })
+            };
+
+            let index = registry.register(name, shader("vertex")?, shader("fragment")?);
+            registry.techniques[index].instancing = entry
+                .get("instancing")
+                .and_then(serde_json::Value::as_bool)
+                .unwrap_or(false);
+        }
+
+        Ok(registry)
+    }
+
+    /// Adds a technique or replaces the shaders of an existing one with the same name
+    pub fn register<P: Into<PathBuf>>(&mut self, name: &str, vertex: P, fragment: P) -> usize {
+        let technique = Technique {
+            name: name.to_string(),
+            variants: ProgramVariants::new(vertex, fragment),
+            instancing: false,
+        };
+
+        if let Some(&index) = self.names.get(name) {
+            self.techniques[index] = technique;
+            return index;
+        }
+
+        let index = self.techniques.len();
+        self.techniques.push(technique);
+        self.names.insert(name.to_string(), index);
+
+        index
+    }
+
+    pub fn find(&self, name: &str) -> Option<usize> {
+        self.names.get(name).copied()
+    }
+
+    pub fn get(&self, technique: usize) -> &Technique {
+        &self.techniques[technique]
+    }
+
+    /// Returns index of the technique's permutation in `programs`, building it if needed
+    pub fn program(
+        &mut self,
+        technique: usize,
+        features: ShaderFeatures,
+        programs: &mut Vec<Program>,
+        setup: impl FnOnce(&mut Program),
+    ) -> Result<usize, String> {
+        let technique = &mut self.techniques[technique];
+
+        technique
+            .variants
+            .get_or_build(features, programs, setup)
+            .map_err(|log| format!("Technique \"{}\": {log}", technique.name))
+    }
+
+    /// Number of program permutations built for all techniques
+    pub fn permutation_count(&self) -> usize {
+        self.techniques
+            .iter()
+            .map(|technique| technique.variants.len())
+            .sum()
+    }
+}
@@ -1,7 +1,64 @@
+use std::cell::Cell;
+
 use gl::types::GLenum;
 
+use crate::leak::{self, GlKind};
+
+/// Texture sampling style, nearest is the closest to PSX hardware
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum FilterPolicy {
+    #[default]
+    Nearest,
+    /// Filters from the glTF sampler
+    Authored,
+    /// N64 styled 3-point bilinear filtering done in the fragment shader
+    ThreePoint,
+}
+
+impl FilterPolicy {
+    pub fn next(self) -> Self {
+        match self {
+            FilterPolicy::Nearest => FilterPolicy::Authored,
+            FilterPolicy::Authored => FilterPolicy::ThreePoint,
+            FilterPolicy::ThreePoint => FilterPolicy::Nearest,
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "nearest" => Some(FilterPolicy::Nearest),
+            "authored" => Some(FilterPolicy::Authored),
+            "three_point" => Some(FilterPolicy::ThreePoint),
+            _ => None,
+        }
+    }
+}
+
+/// Internal (GPU side) format together with the layout of the uploaded pixels
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PixelFormat {
+    pub internal_format: GLenum,
+    pub format: GLenum,
+    pub gl_type: GLenum,
+}
+
+impl PixelFormat {
+    pub const fn new(internal_format: GLenum, format: GLenum, gl_type: GLenum) -> Self {
+        Self {
+            internal_format,
+            format,
+            gl_type,
+        }
+    }
+}
+
 #[derive(Debug)]
-pub struct Texture2D(u32);
+pub struct Texture2D {
+    id: u32,
+    mag_filter: GLenum,
+    min_filter: GLenum,
+    applied_filters: Cell<(GLenum, GLenum)>,
+}
 
 impl Texture2D {
     pub fn new(
@@ -10,8 +67,7 @@ impl Texture2D {
         mag_filter: GLenum,
         min_filter: GLenum,
         data: Option<&[u8]>,
-        format: GLenum,
-        gl_type: GLenum,
+        format: PixelFormat,
         dimensions: (u32, u32),
     ) -> Self {
         let texture_id = {
@@ -20,6 +76,7 @@ impl Texture2D {
                 gl::GenTextures(1, &mut id);
                 assert_ne!(id, 0);
             }
+            leak::created(GlKind::Texture);
             id
         };
 
@@ -31,34 +88,153 @@ impl Texture2D {
 
             gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, min_filter as i32);
             gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, mag_filter as i32);
-            // gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST as i32);
-            // gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as i32);
 
+            // Image rows are tightly packed, e.g. RGB images with odd widths
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
             gl::TexImage2D(
                 gl::TEXTURE_2D,
                 0,
-                format as i32,
+                format.internal_format as i32,
                 dimensions.0 as i32,
                 dimensions.1 as i32,
                 0,
-                format,
-                gl_type,
+                format.format,
+                format.gl_type,
                 data.map(|x| x.as_ptr()).unwrap_or(std::ptr::null()) as *const _,
             );
 
-            if min_filter != gl::NEAREST || mag_filter != gl::NEAREST {
+            // Mipmaps are generated even if a policy overrides the filters later,
+            // so switching back to authored filtering at runtime stays possible
+            if is_mipmap_filter(min_filter) {
                 gl::GenerateMipmap(gl::TEXTURE_2D);
             }
 
             gl::BindTexture(gl::TEXTURE_2D, 0);
         }
 
-        Self(texture_id)
+        Self {
+            id: texture_id,
+            mag_filter,
+            min_filter,
+            applied_filters: Cell::new((mag_filter, min_filter)),
+        }
     }
+
+    /// Updates sampling filters of the texture if the policy demands other ones
+    pub(crate) fn apply_filter_policy(&self, policy: FilterPolicy) {
+        let filters = match policy {
+            FilterPolicy::Nearest | FilterPolicy::ThreePoint => (gl::NEAREST, gl::NEAREST),
+            FilterPolicy::Authored => (self.mag_filter, self.min_filter),
+        };
+
+        if self.applied_filters.get() == filters {
+            return;
+        }
+
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_2D, self.id);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, filters.0 as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, filters.1 as i32);
+        }
+
+        self.applied_filters.set(filters);
+    }
+}
+
+fn is_mipmap_filter(filter: GLenum) -> bool {
+    matches!(
+        filter,
+        gl::NEAREST_MIPMAP_NEAREST
+            | gl::LINEAR_MIPMAP_NEAREST
+            | gl::NEAREST_MIPMAP_LINEAR
+            | gl::LINEAR_MIPMAP_LINEAR
+    )
 }
 
 impl crate::GlObject for Texture2D {
     fn glid(&self) -> gl::types::GLuint {
-        self.0
+        self.id
+    }
+}
+
+impl Drop for Texture2D {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteTextures(1, &self.id);
+        }
+        leak::deleted(GlKind::Texture);
+    }
+}
+
+/// Six square faces sampled by direction, nearest filtered
+#[derive(Debug)]
+pub struct TextureCube {
+    id: u32,
+}
+
+impl TextureCube {
+    /// Faces in +X, -X, +Y, -Y, +Z, -Z order, `size` pixels wide and high
+    pub fn new(faces: [&[u8]; 6], format: PixelFormat, size: u32) -> Self {
+        let texture_id = {
+            let mut id = 0;
+            unsafe {
+                gl::GenTextures(1, &mut id);
+                assert_ne!(id, 0);
+            }
+            leak::created(GlKind::Texture);
+            id
+        };
+
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_CUBE_MAP, texture_id);
+
+            for parameter in [gl::TEXTURE_WRAP_S, gl::TEXTURE_WRAP_T, gl::TEXTURE_WRAP_R] {
+                gl::TexParameteri(gl::TEXTURE_CUBE_MAP, parameter, gl::CLAMP_TO_EDGE as i32);
+            }
+            gl::TexParameteri(
+                gl::TEXTURE_CUBE_MAP,
+                gl::TEXTURE_MIN_FILTER,
+                gl::NEAREST as i32,
+            );
+            gl::TexParameteri(
+                gl::TEXTURE_CUBE_MAP,
+                gl::TEXTURE_MAG_FILTER,
+                gl::NEAREST as i32,
+            );
+
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+            for (face, data) in faces.iter().enumerate() {
+                gl::TexImage2D(
+                    gl::TEXTURE_CUBE_MAP_POSITIVE_X + face as u32,
+                    0,
+                    format.internal_format as i32,
+                    size as i32,
+                    size as i32,
+                    0,
+                    format.format,
+                    format.gl_type,
+                    data.as_ptr().cast(),
+                );
+            }
+
+            gl::BindTexture(gl::TEXTURE_CUBE_MAP, 0);
+        }
+
+        Self { id: texture_id }
+    }
+}
+
+impl crate::GlObject for TextureCube {
+    fn glid(&self) -> gl::types::GLuint {
+        self.id
+    }
+}
+
+impl Drop for TextureCube {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteTextures(1, &self.id);
+        }
+        leak::deleted(GlKind::Texture);
     }
 }
@@ -1,3 +1,5 @@
+use crate::leak::{self, GlKind};
+
 #[derive(Debug)]
 #[repr(transparent)]
 pub(crate) struct Vao(u32);
@@ -9,11 +11,19 @@ impl Vao {
             gl::GenVertexArrays(1, &mut vao);
 
             assert_ne!(vao, 0);
+            leak::created(GlKind::VertexArray);
 
             Self(vao)
         }
     }
 
+    /// Binds the array and leaves it bound, for drawing several times in a row
+    pub fn bind(&self) {
+        unsafe {
+            gl::BindVertexArray(self.0);
+        }
+    }
+
     pub fn as_context<F, R>(&mut self, mut closure: F) -> R
     where
         F: FnMut() -> R,
@@ -43,5 +53,6 @@ impl Drop for Vao {
         unsafe {
             gl::DeleteVertexArrays(1, &self.0);
         }
+        leak::deleted(GlKind::VertexArray);
     }
 }
@@ -0,0 +1,181 @@
+use crate::camera::WORLD_UP;
+use crate::collision::{Capsule, CollisionMesh};
+
+use cgmath::prelude::*;
+use cgmath::Vector3;
+
+const PLAYER: Capsule = Capsule {
+    radius: 0.3,
+    height: 1.8,
+};
+/// Meters per second, before the run multiplier
+pub const WALK_SPEED: f32 = 2.0;
+/// Camera height above the feet
+pub const EYE_HEIGHT: f32 = 1.6;
+/// Highest ledge walked onto without jumping
+const STEP_HEIGHT: f32 = 0.35;
+
+const GRAVITY: f32 = 9.81;
+const TERMINAL_VELOCITY: f32 = 30.0;
+const JUMP_SPEED: f32 = 4.5;
+
+/// First person body moved by gravity and collisions
+#[derive(Debug, Default)]
+pub struct Walker {
+    vertical_velocity: f32,
+    grounded: bool,
+}
+
+impl Walker {
+    pub fn is_grounded(&self) -> bool {
+        self.grounded
+    }
+
+    /// Forgets the velocity, e.g. after teleporting
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Walks by the horizontal `displacement`, returns the new eye position
+    pub fn update(
+        &mut self,
+        collision: &CollisionMesh,
+        eye: Vector3<f32>,
+        displacement: Vector3<f32>,
+        jump: bool,
+        delta: f32,
+    ) -> Vector3<f32> {
+        let mut feet = eye - WORLD_UP * EYE_HEIGHT;
+        let displacement = cgmath::vec3(displacement.x, 0.0, displacement.z);
+
+        if jump && self.grounded {
+            self.vertical_velocity = JUMP_SPEED;
+            self.grounded = false;
+        }
+        self.vertical_velocity = (self.vertical_velocity - GRAVITY * delta).max(-TERMINAL_VELOCITY);
+
+        feet = self.walk(collision, feet, displacement);
+
+        let was_grounded = self.grounded;
+        let fall = collision.slide(PLAYER, feet, WORLD_UP * self.vertical_velocity * delta);
+        feet = fall.feet;
+        self.grounded = fall.grounded;
+
+        // Stay on the ground when walking down stairs and slopes instead of falling off each step
+        if was_grounded && !self.grounded && self.vertical_velocity <= 0.0 {
+            let snap = collision.slide(PLAYER, feet, -WORLD_UP * STEP_HEIGHT);
+            if snap.grounded {
+                feet = snap.feet;
+                self.grounded = true;
+            }
+        }
+
+        if (self.grounded && self.vertical_velocity < 0.0)
+            || (fall.ceiling && self.vertical_velocity > 0.0)
+        {
+            self.vertical_velocity = 0.0;
+        }
+
+        feet + WORLD_UP * EYE_HEIGHT
+    }
+
+    /// Horizontal move, tries climbing a step when that gets further
+    fn walk(
+        &self,
+        collision: &CollisionMesh,
+        feet: Vector3<f32>,
+        displacement: Vector3<f32>,
+    ) -> Vector3<f32> {
+        let direct = collision.slide(PLAYER, feet, displacement);
+        if !self.grounded || displacement.magnitude2() == 0.0 {
+            return direct.feet;
+        }
+
+        // The rounded bottom already rides up edges as high as the radius
+        let raise = (STEP_HEIGHT - PLAYER.radius).max(0.0);
+        let raised = collision.slide(PLAYER, feet, WORLD_UP * raise);
+        let stepped = collision.slide(PLAYER, raised.feet, displacement);
+        let lowered = collision.slide(PLAYER, stepped.feet, -WORLD_UP * (raised.feet.y - feet.y));
+
+        let travelled = |to: Vector3<f32>| {
+            let moved = to - feet;
+            cgmath::vec2(moved.x, moved.z).magnitude()
+        };
+        if lowered.grounded && travelled(lowered.feet) > travelled(direct.feet) + 1e-4 {
+            lowered.feet
+        } else {
+            direct.feet
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collision::fixtures::{floor, wall};
+
+    const DELTA: f32 = 1.0 / 60.0;
+
+    /// Floor at 0 with a block of `height` starting at x = 1
+    fn ledge(height: f32) -> CollisionMesh {
+        CollisionMesh::new(
+            [
+                floor(0.0, -10.0..1.0),
+                wall(1.0, 0.0..height),
+                floor(height, 1.0..10.0),
+            ]
+            .into_iter()
+            .flatten(),
+        )
+    }
+
+    /// Walks towards +X for a second, returns the feet position
+    fn walk_forward(walker: &mut Walker, collision: &CollisionMesh) -> Vector3<f32> {
+        let mut eye = cgmath::vec3(0.0, EYE_HEIGHT, 0.0);
+        eye = walker.update(collision, eye, cgmath::vec3(0.0, 0.0, 0.0), false, DELTA);
+        for _ in 0..60 {
+            let step = cgmath::vec3(WALK_SPEED * DELTA, 0.0, 0.0);
+            eye = walker.update(collision, eye, step, false, DELTA);
+        }
+
+        eye - WORLD_UP * EYE_HEIGHT
+    }
+
+    #[test]
+    fn climbs_steps() {
+        let mut walker = Walker::default();
+        let feet = walk_forward(&mut walker, &ledge(0.3));
+
+        assert!(walker.is_grounded());
+        assert!(feet.x > 1.5, "{feet:?}");
+        assert!((feet.y - 0.3).abs() < 1e-3, "{feet:?}");
+    }
+
+    #[test]
+    fn stops_at_high_ledges() {
+        let mut walker = Walker::default();
+        let feet = walk_forward(&mut walker, &ledge(1.0));
+
+        assert!(walker.is_grounded());
+        assert!((feet.x - (1.0 - PLAYER.radius)).abs() < 1e-3, "{feet:?}");
+        assert!(feet.y.abs() < 1e-3, "{feet:?}");
+    }
+
+    #[test]
+    fn jumps_only_from_the_ground() {
+        let collision = ledge(1.0);
+        let mut walker = Walker::default();
+        let still = cgmath::vec3(0.0, 0.0, 0.0);
+
+        let mut eye = cgmath::vec3(0.0, EYE_HEIGHT, 0.0);
+        eye = walker.update(&collision, eye, still, false, DELTA);
+        assert!(walker.is_grounded());
+
+        eye = walker.update(&collision, eye, still, true, DELTA);
+        assert!(!walker.is_grounded());
+        let peak_velocity = walker.vertical_velocity;
+        // Jumping again in the air doesn't add speed
+        walker.update(&collision, eye, still, true, DELTA);
+        assert!(walker.vertical_velocity < peak_velocity);
+    }
+}